@@ -0,0 +1,1623 @@
+use super::number::Number;
+use super::{apply, gensym, is_eq, is_truthy, read_str, Error, Expression, Function, Map};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+macro_rules! ensure_tonicity {
+    ($check_fn:expr) => {{
+        |args: &[Expression]| -> Result<Expression, Error> {
+            let numbers = parse_list_of_numbers(args)?;
+            let first = numbers
+                .first()
+                .ok_or(Error::arity("expected at least one number"))?;
+            let rest = &numbers[1..];
+            fn f(prev: &Number, xs: &[Number]) -> bool {
+                match xs.first() {
+                    Some(x) => $check_fn(prev, x) && f(x, &xs[1..]),
+                    None => true,
+                }
+            }
+            Ok(Expression::Bool(f(first, rest)))
+        }
+    }};
+}
+
+macro_rules! number_fn {
+    ($name:expr, $f:expr) => {{
+        |args: &[Expression]| -> Result<Expression, Error> {
+            let x = parse_only_arg(args).and_then(parse_single_number)?;
+            number_result($name, $f(&x))
+        }
+    }};
+}
+
+// Transcendental functions are always inexact.
+macro_rules! float_fn {
+    ($name:expr, $f:expr) => {{
+        |args: &[Expression]| -> Result<Expression, Error> {
+            let x = parse_only_arg(args).and_then(parse_single_float)?;
+            number_result($name, Number::Float($f(x)))
+        }
+    }};
+}
+
+macro_rules! division_fn {
+    ($name:expr, $f:expr) => {{
+        |args: &[Expression]| -> Result<Expression, Error> {
+            let (a, b) = match parse_list_of_numbers(args)?.as_slice() {
+                [a, b] => (a.clone(), b.clone()),
+                _ => {
+                    return Err(Error::arity(format!(
+                        "{} expects 2 arguments, got {}",
+                        $name,
+                        args.len()
+                    )))
+                }
+            };
+            let quotient = a
+                .checked_div(&b)
+                .ok_or(Error::arithmetic(format!("{}: division by zero", $name)))?;
+            number_result($name, $f(&a, &b, &quotient))
+        }
+    }};
+}
+
+macro_rules! type_predicate {
+    ($($pattern:pat)|+) => {{
+        |args: &[Expression]| -> Result<Expression, Error> {
+            let x = parse_only_arg(args)?;
+            Ok(Expression::Bool(matches!(x, $($pattern)|+)))
+        }
+    }};
+}
+
+fn builtin(
+    name: &str,
+    f: impl Fn(&[Expression]) -> Result<Expression, Error> + 'static,
+) -> Expression {
+    Expression::Function(Function::new(name, f))
+}
+
+// The native functions and constants every environment starts out with.
+pub(super) fn builtins() -> HashMap<String, Expression> {
+    let mut data: HashMap<String, Expression> = HashMap::new();
+    data.insert(
+        "+".to_string(),
+        builtin("+", |args: &[Expression]| -> Result<Expression, Error> {
+            let sum = parse_list_of_numbers(args)?
+                .iter()
+                .fold(Number::Int(0), |sum, a| &sum + a);
+
+            number_result("+", sum)
+        }),
+    );
+    data.insert(
+        "-".to_string(),
+        builtin("-", |args: &[Expression]| -> Result<Expression, Error> {
+            let numbers = parse_list_of_numbers(args)?;
+            let first = numbers
+                .first()
+                .ok_or(Error::arity("expected at least one number"))?;
+            if numbers.len() == 1 {
+                return Ok(Expression::Number(-first));
+            }
+            let sum_of_rest = numbers[1..].iter().fold(Number::Int(0), |sum, a| &sum + a);
+
+            number_result("-", first - &sum_of_rest)
+        }),
+    );
+    data.insert(
+        "*".to_string(),
+        builtin("*", |args: &[Expression]| -> Result<Expression, Error> {
+            let product = parse_list_of_numbers(args)?
+                .iter()
+                .fold(Number::Int(1), |product, a| &product * a);
+
+            number_result("*", product)
+        }),
+    );
+    data.insert(
+        "/".to_string(),
+        builtin("/", |args: &[Expression]| -> Result<Expression, Error> {
+            let numbers = parse_list_of_numbers(args)?;
+            let (first, rest) = match numbers.as_slice() {
+                [] => return Err(Error::arity("expected at least one number")),
+                [x] => (Number::Int(1), std::slice::from_ref(x)),
+                [first, rest @ ..] => (first.clone(), rest),
+            };
+            let quotient = rest.iter().try_fold(first, |quotient, a| {
+                quotient
+                    .checked_div(a)
+                    .ok_or(Error::arithmetic("/: division by zero"))
+            })?;
+
+            number_result("/", quotient)
+        }),
+    );
+    data.insert(
+        "quotient".to_string(),
+        builtin(
+            "quotient",
+            division_fn!("quotient", |_, _, q: &Number| q.trunc()),
+        ),
+    );
+    data.insert(
+        "remainder".to_string(),
+        builtin(
+            "remainder",
+            division_fn!("remainder", |a: &Number, b: &Number, q: &Number| a - &(b
+                * &q.trunc())),
+        ),
+    );
+    data.insert(
+        "mod".to_string(),
+        builtin(
+            "mod",
+            division_fn!("mod", |a: &Number, b: &Number, q: &Number| a - &(b * &q
+                .floor())),
+        ),
+    );
+    data.insert(
+        "min".to_string(),
+        builtin("min", |args: &[Expression]| -> Result<Expression, Error> {
+            let numbers = parse_list_of_numbers(args)?;
+            let first = numbers
+                .first()
+                .ok_or(Error::arity("expected at least one number"))?;
+            let min = numbers.iter().fold(first, |a, b| if b < a { b } else { a });
+
+            Ok(Expression::Number(inexact_if_any(min, &numbers)))
+        }),
+    );
+    data.insert(
+        "max".to_string(),
+        builtin("max", |args: &[Expression]| -> Result<Expression, Error> {
+            let numbers = parse_list_of_numbers(args)?;
+            let first = numbers
+                .first()
+                .ok_or(Error::arity("expected at least one number"))?;
+            let max = numbers.iter().fold(first, |a, b| if b > a { b } else { a });
+
+            Ok(Expression::Number(inexact_if_any(max, &numbers)))
+        }),
+    );
+    data.insert(
+        "abs".to_string(),
+        builtin("abs", number_fn!("abs", Number::abs)),
+    );
+    data.insert(
+        "floor".to_string(),
+        builtin("floor", number_fn!("floor", Number::floor)),
+    );
+    data.insert(
+        "ceil".to_string(),
+        builtin("ceil", number_fn!("ceil", Number::ceil)),
+    );
+    data.insert(
+        "round".to_string(),
+        builtin("round", number_fn!("round", Number::round)),
+    );
+    data.insert(
+        "truncate".to_string(),
+        builtin("truncate", number_fn!("truncate", Number::trunc)),
+    );
+    data.insert(
+        "sqrt".to_string(),
+        builtin("sqrt", number_fn!("sqrt", Number::sqrt)),
+    );
+    data.insert(
+        "exp".to_string(),
+        builtin("exp", float_fn!("exp", f64::exp)),
+    );
+    data.insert(
+        "expt".to_string(),
+        builtin("expt", |args: &[Expression]| -> Result<Expression, Error> {
+            match parse_list_of_numbers(args)?.as_slice() {
+                [base, exponent] => {
+                    let power = base
+                        .pow(exponent)
+                        .ok_or(Error::arithmetic("expt: division by zero"))?;
+                    number_result("expt", power)
+                }
+                _ => Err(Error::arity(format!(
+                    "expt expects 2 arguments, got {}",
+                    args.len()
+                ))),
+            }
+        }),
+    );
+    data.insert(
+        "log".to_string(),
+        builtin("log", |args: &[Expression]| -> Result<Expression, Error> {
+            match parse_list_of_floats(args)?.as_slice() {
+                [x] => number_result("log", Number::Float(x.ln())),
+                [x, base] => number_result("log", Number::Float(x.log(*base))),
+                _ => Err(Error::arity(format!(
+                    "log expects 1 or 2 arguments, got {}",
+                    args.len()
+                ))),
+            }
+        }),
+    );
+    data.insert(
+        "sin".to_string(),
+        builtin("sin", float_fn!("sin", f64::sin)),
+    );
+    data.insert(
+        "cos".to_string(),
+        builtin("cos", float_fn!("cos", f64::cos)),
+    );
+    data.insert(
+        "tan".to_string(),
+        builtin("tan", float_fn!("tan", f64::tan)),
+    );
+    data.insert(
+        "asin".to_string(),
+        builtin("asin", float_fn!("asin", f64::asin)),
+    );
+    data.insert(
+        "acos".to_string(),
+        builtin("acos", float_fn!("acos", f64::acos)),
+    );
+    data.insert(
+        "atan".to_string(),
+        builtin("atan", |args: &[Expression]| -> Result<Expression, Error> {
+            match parse_list_of_floats(args)?.as_slice() {
+                [x] => number_result("atan", Number::Float(x.atan())),
+                [y, x] => number_result("atan", Number::Float(y.atan2(*x))),
+                _ => Err(Error::arity(format!(
+                    "atan expects 1 or 2 arguments, got {}",
+                    args.len()
+                ))),
+            }
+        }),
+    );
+    data.insert(
+        "exact->inexact".to_string(),
+        builtin(
+            "exact->inexact",
+            number_fn!("exact->inexact", Number::to_inexact),
+        ),
+    );
+    data.insert(
+        "inexact->exact".to_string(),
+        builtin(
+            "inexact->exact",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let x = parse_only_arg(args).and_then(parse_single_number)?;
+                let exact = x.to_exact().ok_or(Error::arithmetic(format!(
+                    "inexact->exact: {} has no exact representation",
+                    x
+                )))?;
+                Ok(Expression::Number(exact))
+            },
+        ),
+    );
+    data.insert(
+        "exact?".to_string(),
+        builtin(
+            "exact?",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let x = parse_only_arg(args).and_then(parse_single_number)?;
+                Ok(Expression::Bool(x.is_exact()))
+            },
+        ),
+    );
+    data.insert(
+        "inexact?".to_string(),
+        builtin(
+            "inexact?",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let x = parse_only_arg(args).and_then(parse_single_number)?;
+                Ok(Expression::Bool(!x.is_exact()))
+            },
+        ),
+    );
+    data.insert(
+        "integer?".to_string(),
+        builtin(
+            "integer?",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let x = parse_only_arg(args).and_then(parse_single_number)?;
+                Ok(Expression::Bool(x.is_integer()))
+            },
+        ),
+    );
+    data.insert(
+        "pi".to_string(),
+        Expression::Number(Number::Float(std::f64::consts::PI)),
+    );
+    data.insert(
+        "e".to_string(),
+        Expression::Number(Number::Float(std::f64::consts::E)),
+    );
+    data.insert(
+        "=".to_string(),
+        builtin("=", ensure_tonicity!(|a, b| a == b)),
+    );
+    data.insert(
+        ">".to_string(),
+        builtin(">", ensure_tonicity!(|a, b| a > b)),
+    );
+    data.insert(
+        ">=".to_string(),
+        builtin(">=", ensure_tonicity!(|a, b| a >= b)),
+    );
+    data.insert(
+        "<".to_string(),
+        builtin("<", ensure_tonicity!(|a, b| a < b)),
+    );
+    data.insert(
+        "<=".to_string(),
+        builtin("<=", ensure_tonicity!(|a, b| a <= b)),
+    );
+    data.insert(
+        "print".to_string(),
+        builtin(
+            "print",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                write_output(&mut io::stdout(), &display_args(args))
+            },
+        ),
+    );
+    data.insert(
+        "println".to_string(),
+        builtin(
+            "println",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                write_output(&mut io::stdout(), &format!("{}\n", display_args(args)))
+            },
+        ),
+    );
+    data.insert(
+        "display".to_string(),
+        builtin(
+            "display",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let x = parse_only_arg(args)?;
+                write_output(&mut io::stdout(), &x.display())
+            },
+        ),
+    );
+    data.insert(
+        "newline".to_string(),
+        builtin(
+            "newline",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                if !args.is_empty() {
+                    return Err(Error::arity(format!(
+                        "newline expects no arguments, got {}",
+                        args.len()
+                    )));
+                }
+                write_output(&mut io::stdout(), "\n")
+            },
+        ),
+    );
+    data.insert(
+        "eprint".to_string(),
+        builtin(
+            "eprint",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                write_output(&mut io::stderr(), &display_args(args))
+            },
+        ),
+    );
+    data.insert(
+        "eprintln".to_string(),
+        builtin(
+            "eprintln",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                write_output(&mut io::stderr(), &format!("{}\n", display_args(args)))
+            },
+        ),
+    );
+
+    data.insert(
+        "throw".to_string(),
+        builtin(
+            "throw",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                // Throwing a caught error re-raises it as it was.
+                match parse_only_arg(args)? {
+                    Expression::Error(e) => Err(e.as_ref().clone()),
+                    exp => Err(Error::thrown(exp.clone())),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "error-message".to_string(),
+        builtin(
+            "error-message",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let e = parse_only_arg(args).and_then(parse_single_error)?;
+                Ok(Expression::Str(e.message()))
+            },
+        ),
+    );
+    data.insert(
+        "error-kind".to_string(),
+        builtin(
+            "error-kind",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let e = parse_only_arg(args).and_then(parse_single_error)?;
+                Ok(Expression::Symbol(e.kind_name().to_string()))
+            },
+        ),
+    );
+    data.insert(
+        "repr".to_string(),
+        builtin("repr", |args: &[Expression]| -> Result<Expression, Error> {
+            let x = parse_only_arg(args)?;
+            Ok(Expression::Str(x.to_string()))
+        }),
+    );
+    data.insert(
+        "read-string".to_string(),
+        builtin(
+            "read-string",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let s = parse_only_arg(args).and_then(parse_single_string)?;
+                // Positions within the string would be taken for positions
+                // in the program.
+                read_str(&s).map_err(|e| Error { span: None, ..e })
+            },
+        ),
+    );
+    data.insert(
+        "error".to_string(),
+        builtin(
+            "error",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                Err(Error::user(display_args(args)))
+            },
+        ),
+    );
+    data.insert(
+        "str-concat".to_string(),
+        builtin(
+            "str-concat",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let strs = parse_list_of_strings(args)?;
+                Ok(Expression::Str(strs.concat()))
+            },
+        ),
+    );
+    data.insert(
+        "str-length".to_string(),
+        builtin(
+            "str-length",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let s = parse_only_arg(args).and_then(parse_single_string)?;
+                Ok(Expression::Number(Number::from(s.chars().count())))
+            },
+        ),
+    );
+    data.insert(
+        "substring".to_string(),
+        builtin(
+            "substring",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                if args.len() < 2 || args.len() > 3 {
+                    return Err(Error::arity(format!(
+                        "substring expects 2 or 3 arguments, got {}",
+                        args.len()
+                    )));
+                }
+                let chars: Vec<char> = parse_single_string(&args[0])?.chars().collect();
+                let start = parse_single_index(&args[1])?;
+                let end = match args.get(2) {
+                    Some(exp) => parse_single_index(exp)?,
+                    None => chars.len(),
+                };
+                if start > end || end > chars.len() {
+                    return Err(Error::type_error(format!(
+                        "substring range {}..{} out of bounds for length {}",
+                        start,
+                        end,
+                        chars.len()
+                    )));
+                }
+                Ok(Expression::Str(chars[start..end].iter().collect()))
+            },
+        ),
+    );
+    data.insert(
+        "str-split".to_string(),
+        builtin(
+            "str-split",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let strs = parse_list_of_strings(args)?;
+                let parts: Vec<Expression> = match strs.as_slice() {
+                    [s] => s
+                        .split_whitespace()
+                        .map(|x| Expression::Str(x.to_string()))
+                        .collect(),
+                    [s, sep] if !sep.is_empty() => s
+                        .split(sep.as_str())
+                        .map(|x| Expression::Str(x.to_string()))
+                        .collect(),
+                    [_, _] => return Err(Error::type_error("separator must not be empty")),
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "str-split expects 1 or 2 arguments, got {}",
+                            strs.len()
+                        )))
+                    }
+                };
+                Ok(Expression::List(parts.into()))
+            },
+        ),
+    );
+    data.insert(
+        "str-join".to_string(),
+        builtin(
+            "str-join",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let (list, sep) = match args {
+                    [Expression::List(list)] => (list, String::new()),
+                    [Expression::List(list), sep] => (list, parse_single_string(sep)?),
+                    _ => {
+                        return Err(Error::type_error(
+                            "str-join expects a list of strings and an optional separator",
+                        ))
+                    }
+                };
+                Ok(Expression::Str(parse_list_of_strings(list)?.join(&sep)))
+            },
+        ),
+    );
+    data.insert(
+        "str-upcase".to_string(),
+        builtin(
+            "str-upcase",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let s = parse_only_arg(args).and_then(parse_single_string)?;
+                Ok(Expression::Str(s.to_uppercase()))
+            },
+        ),
+    );
+    data.insert(
+        "str-downcase".to_string(),
+        builtin(
+            "str-downcase",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let s = parse_only_arg(args).and_then(parse_single_string)?;
+                Ok(Expression::Str(s.to_lowercase()))
+            },
+        ),
+    );
+    data.insert(
+        "str-trim".to_string(),
+        builtin(
+            "str-trim",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let s = parse_only_arg(args).and_then(parse_single_string)?;
+                Ok(Expression::Str(s.trim().to_string()))
+            },
+        ),
+    );
+    data.insert(
+        "number->string".to_string(),
+        builtin(
+            "number->string",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let n = parse_only_arg(args).and_then(parse_single_number)?;
+                Ok(Expression::Str(n.to_string()))
+            },
+        ),
+    );
+    data.insert(
+        "string->number".to_string(),
+        builtin(
+            "string->number",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let s = parse_only_arg(args).and_then(parse_single_string)?;
+                match Number::parse(s.trim()) {
+                    Some(n) => Ok(Expression::Number(n)),
+                    None => Ok(Expression::Bool(false)),
+                }
+            },
+        ),
+    );
+
+    data.insert(
+        "eq?".to_string(),
+        builtin("eq?", |args: &[Expression]| -> Result<Expression, Error> {
+            match args {
+                [a, b] => Ok(Expression::Bool(is_eq(a, b))),
+                _ => Err(Error::arity(format!(
+                    "eq? expects 2 arguments, got {}",
+                    args.len()
+                ))),
+            }
+        }),
+    );
+    data.insert(
+        "equal?".to_string(),
+        builtin(
+            "equal?",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                match args {
+                    [a, b] => Ok(Expression::Bool(a == b)),
+                    _ => Err(Error::arity(format!(
+                        "equal? expects 2 arguments, got {}",
+                        args.len()
+                    ))),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "not".to_string(),
+        builtin("not", |args: &[Expression]| -> Result<Expression, Error> {
+            let x = parse_only_arg(args)?;
+            Ok(Expression::Bool(!is_truthy(x)))
+        }),
+    );
+    data.insert(
+        "nil?".to_string(),
+        builtin("nil?", type_predicate!(Expression::Nil)),
+    );
+    data.insert(
+        "bool?".to_string(),
+        builtin("bool?", type_predicate!(Expression::Bool(_))),
+    );
+    data.insert(
+        "number?".to_string(),
+        builtin("number?", type_predicate!(Expression::Number(_))),
+    );
+    data.insert(
+        "symbol?".to_string(),
+        builtin("symbol?", type_predicate!(Expression::Symbol(_))),
+    );
+    data.insert(
+        "keyword?".to_string(),
+        builtin("keyword?", type_predicate!(Expression::Keyword(_))),
+    );
+    data.insert(
+        "string?".to_string(),
+        builtin("string?", type_predicate!(Expression::Str(_))),
+    );
+    data.insert(
+        "list?".to_string(),
+        builtin("list?", type_predicate!(Expression::List(_))),
+    );
+    data.insert(
+        "fn?".to_string(),
+        builtin(
+            "fn?",
+            type_predicate!(Expression::Function(_) | Expression::Lambda(_)),
+        ),
+    );
+    data.insert(
+        "box?".to_string(),
+        builtin("box?", type_predicate!(Expression::Box(_))),
+    );
+    data.insert(
+        "array?".to_string(),
+        builtin("array?", type_predicate!(Expression::Array(_))),
+    );
+    data.insert(
+        "vector?".to_string(),
+        builtin("vector?", type_predicate!(Expression::Vector(_))),
+    );
+    data.insert(
+        "map?".to_string(),
+        builtin("map?", type_predicate!(Expression::Map(_))),
+    );
+    data.insert(
+        "error?".to_string(),
+        builtin("error?", type_predicate!(Expression::Error(_))),
+    );
+
+    data.insert(
+        "gensym".to_string(),
+        builtin(
+            "gensym",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let prefix = match args {
+                    [] => "G".to_string(),
+                    [prefix] => parse_single_string(prefix)?,
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "gensym expects at most 1 argument, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                Ok(Expression::Symbol(gensym(&prefix)))
+            },
+        ),
+    );
+    data.insert(
+        "list".to_string(),
+        builtin("list", |args: &[Expression]| -> Result<Expression, Error> {
+            Ok(Expression::List(args.to_vec().into()))
+        }),
+    );
+    data.insert(
+        "cons".to_string(),
+        builtin("cons", |args: &[Expression]| -> Result<Expression, Error> {
+            match args {
+                [head, tail] => {
+                    let mut list = vec![head.clone()];
+                    list.extend(parse_single_list(tail)?);
+                    Ok(Expression::List(list.into()))
+                }
+                _ => Err(Error::arity(format!(
+                    "cons expects 2 arguments, got {}",
+                    args.len()
+                ))),
+            }
+        }),
+    );
+    let first = builtin("car", |args: &[Expression]| -> Result<Expression, Error> {
+        let list = parse_only_arg(args).and_then(parse_single_list)?;
+        list.first()
+            .cloned()
+            .ok_or(Error::type_error("expected a non-empty list"))
+    });
+    data.insert("car".to_string(), first.clone());
+    data.insert("first".to_string(), first);
+    let rest = builtin("cdr", |args: &[Expression]| -> Result<Expression, Error> {
+        let list = parse_only_arg(args).and_then(parse_single_list)?;
+        match list.split_first() {
+            Some((_, rest)) => Ok(Expression::List(rest.to_vec().into())),
+            None => Err(Error::type_error("expected a non-empty list")),
+        }
+    });
+    data.insert("cdr".to_string(), rest.clone());
+    data.insert("rest".to_string(), rest);
+    data.insert(
+        "empty?".to_string(),
+        builtin(
+            "empty?",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let list = parse_only_arg(args).and_then(parse_single_list)?;
+                Ok(Expression::Bool(list.is_empty()))
+            },
+        ),
+    );
+    data.insert(
+        "length".to_string(),
+        builtin(
+            "length",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let list = parse_only_arg(args).and_then(parse_single_list)?;
+                Ok(Expression::Number(Number::from(list.len())))
+            },
+        ),
+    );
+    data.insert(
+        "nth".to_string(),
+        builtin("nth", |args: &[Expression]| -> Result<Expression, Error> {
+            match args {
+                [list, idx] => {
+                    let list = parse_single_list(list)?;
+                    let idx = parse_single_index(idx)?;
+                    list.get(idx).cloned().ok_or(Error::type_error(format!(
+                        "index {} out of bounds for length {}",
+                        idx,
+                        list.len()
+                    )))
+                }
+                _ => Err(Error::arity(format!(
+                    "nth expects 2 arguments, got {}",
+                    args.len()
+                ))),
+            }
+        }),
+    );
+    data.insert(
+        "append".to_string(),
+        builtin(
+            "append",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let mut res: Vec<Expression> = vec![];
+                for list in args {
+                    res.extend(parse_single_list(list)?);
+                }
+                Ok(Expression::List(res.into()))
+            },
+        ),
+    );
+    data.insert(
+        "reverse".to_string(),
+        builtin(
+            "reverse",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let mut list = parse_only_arg(args).and_then(parse_single_list)?;
+                list.reverse();
+                Ok(Expression::List(list.into()))
+            },
+        ),
+    );
+    data.insert(
+        "map".to_string(),
+        builtin("map", |args: &[Expression]| -> Result<Expression, Error> {
+            let (f, lists) = match args.split_first() {
+                Some((f, lists)) if !lists.is_empty() => (f, lists),
+                _ => return Err(Error::arity("map expects a function and at least one list")),
+            };
+            let lists = lists
+                .iter()
+                .map(parse_single_list)
+                .collect::<Result<Vec<_>, Error>>()?;
+            // Stops at the end of the shortest list.
+            let len = lists.iter().map(|list| list.len()).min().unwrap_or(0);
+            let res = (0..len)
+                .map(|i| {
+                    let f_args: Vec<Expression> =
+                        lists.iter().map(|list| list[i].clone()).collect();
+                    apply(f, &f_args)
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            Ok(Expression::List(res.into()))
+        }),
+    );
+    data.insert(
+        "filter".to_string(),
+        builtin(
+            "filter",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let (f, list) = match args {
+                    [f, list] => (f, parse_single_list(list)?),
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "filter expects 2 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                let mut res: Vec<Expression> = vec![];
+                for x in list {
+                    if is_truthy(&apply(f, std::slice::from_ref(&x))?) {
+                        res.push(x);
+                    }
+                }
+                Ok(Expression::List(res.into()))
+            },
+        ),
+    );
+    data.insert(
+        "reduce".to_string(),
+        builtin(
+            "reduce",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                // `(reduce f init list)`, or `(reduce f list)` starting from the
+                // first element.
+                let (f, init, list) = match args {
+                    [f, init, list] => (f, init.clone(), parse_single_list(list)?),
+                    [f, list] => {
+                        let list = parse_single_list(list)?;
+                        match list.split_first() {
+                            Some((init, rest)) => (f, init.clone(), rest.to_vec()),
+                            None => {
+                                return Err(Error::type_error(
+                                    "reduce of an empty list needs an initial value",
+                                ))
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "reduce expects 2 or 3 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                list.into_iter()
+                    .try_fold(init, |acc, x| apply(f, &[acc, x]))
+            },
+        ),
+    );
+    data.insert(
+        "apply".to_string(),
+        builtin(
+            "apply",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                // `(apply f a b '(c d))` calls `(f a b c d)`.
+                match args {
+                    [f, spread @ .., list] => {
+                        let mut f_args = spread.to_vec();
+                        f_args.extend(parse_single_list(list)?);
+                        apply(f, &f_args)
+                    }
+                    _ => Err(Error::arity(
+                        "apply expects a function and a list of arguments",
+                    )),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "range".to_string(),
+        builtin(
+            "range",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let numbers = parse_list_of_numbers(args)?;
+                let (start, end, step) = match numbers.as_slice() {
+                    [end] => (Number::Int(0), end.clone(), Number::Int(1)),
+                    [start, end] => (start.clone(), end.clone(), Number::Int(1)),
+                    [start, end, step] => (start.clone(), end.clone(), step.clone()),
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "range expects 1 to 3 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                if step.is_zero() {
+                    return Err(Error::type_error("range step must not be zero"));
+                }
+                let ascending = step > Number::Int(0);
+                // Reserve room for all the elements first, so that a range too
+                // long to fit is an error instead of running out of memory.
+                let len = ((end.to_f64() - start.to_f64()) / step.to_f64()).ceil();
+                let len = if len > 0.0 { len as usize } else { 0 };
+                let mut res: Vec<Expression> = Vec::new();
+                res.try_reserve_exact(len).map_err(|_| {
+                    Error::arithmetic(format!("range: {} elements are too many", len))
+                })?;
+                let mut x = start;
+                while (ascending && x < end) || (!ascending && x > end) {
+                    let next = &x + &step;
+                    res.push(Expression::Number(x));
+                    x = next;
+                }
+                Ok(Expression::List(res.into()))
+            },
+        ),
+    );
+
+    data.insert(
+        "read-line".to_string(),
+        builtin(
+            "read-line",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                if !args.is_empty() {
+                    return Err(Error::arity(format!(
+                        "read-line expects no arguments, got {}",
+                        args.len()
+                    )));
+                }
+                let mut line = String::new();
+                let read = io::stdin()
+                    .read_line(&mut line)
+                    .map_err(|e| Error::io("cannot read stdin", &e))?;
+                // `nil` at the end of input, so a loop can tell it from an
+                // empty line.
+                if read == 0 {
+                    return Ok(Expression::Nil);
+                }
+                Ok(Expression::Str(strip_line_ending(&line).to_string()))
+            },
+        ),
+    );
+    data.insert(
+        "read-file".to_string(),
+        builtin(
+            "read-file",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let path = parse_only_arg(args).and_then(parse_single_string)?;
+                fs::read_to_string(&path)
+                    .map(Expression::Str)
+                    .map_err(|e| Error::io(&format!("cannot read {}", path), &e))
+            },
+        ),
+    );
+    data.insert(
+        "read-lines".to_string(),
+        builtin(
+            "read-lines",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let path = parse_only_arg(args).and_then(parse_single_string)?;
+                let source = fs::read_to_string(&path)
+                    .map_err(|e| Error::io(&format!("cannot read {}", path), &e))?;
+                Ok(Expression::List(
+                    source
+                        .lines()
+                        .map(|line| Expression::Str(line.to_string()))
+                        .collect(),
+                ))
+            },
+        ),
+    );
+    data.insert(
+        "for-each-line".to_string(),
+        builtin(
+            "for-each-line",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                // `(for-each-line path f)` calls f on each line without reading
+                // the whole file into memory.
+                let (path, f) = match args {
+                    [path, f] => (parse_single_string(path)?, f),
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "for-each-line expects 2 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                let file = fs::File::open(&path)
+                    .map_err(|e| Error::io(&format!("cannot read {}", path), &e))?;
+                for line in io::BufReader::new(file).lines() {
+                    let line = line.map_err(|e| Error::io(&format!("cannot read {}", path), &e))?;
+                    apply(f, &[Expression::Str(line)])?;
+                }
+                Ok(Expression::Nil)
+            },
+        ),
+    );
+    data.insert(
+        "write-file".to_string(),
+        builtin(
+            "write-file",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let (path, contents) = match parse_list_of_strings(args)?.as_slice() {
+                    [path, contents] => (path.clone(), contents.clone()),
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "write-file expects 2 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                fs::write(&path, contents)
+                    .map_err(|e| Error::io(&format!("cannot write {}", path), &e))?;
+                Ok(Expression::Nil)
+            },
+        ),
+    );
+    data.insert(
+        "append-file".to_string(),
+        builtin(
+            "append-file",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let (path, contents) = match parse_list_of_strings(args)?.as_slice() {
+                    [path, contents] => (path.clone(), contents.clone()),
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "append-file expects 2 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&path)
+                    .and_then(|mut file| file.write_all(contents.as_bytes()))
+                    .map_err(|e| Error::io(&format!("cannot write {}", path), &e))?;
+                Ok(Expression::Nil)
+            },
+        ),
+    );
+    data.insert(
+        "file-exists?".to_string(),
+        builtin(
+            "file-exists?",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let path = parse_only_arg(args).and_then(parse_single_string)?;
+                Ok(Expression::Bool(Path::new(&path).exists()))
+            },
+        ),
+    );
+
+    data.insert(
+        "box".to_string(),
+        builtin("box", |args: &[Expression]| -> Result<Expression, Error> {
+            let exp = parse_only_arg(args)?;
+            Ok(Expression::Box(Rc::new(RefCell::new(exp.clone()))))
+        }),
+    );
+    data.insert(
+        "unbox".to_string(),
+        builtin(
+            "unbox",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let cell = parse_only_arg(args).and_then(parse_single_box)?;
+                let exp = cell.borrow().clone();
+                Ok(exp)
+            },
+        ),
+    );
+    data.insert(
+        "set-box!".to_string(),
+        builtin(
+            "set-box!",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                match args {
+                    [cell, exp] => {
+                        parse_single_box(cell)?.replace(exp.clone());
+                        Ok(Expression::Nil)
+                    }
+                    _ => Err(Error::arity(format!(
+                        "set-box! expects 2 arguments, got {}",
+                        args.len()
+                    ))),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "array".to_string(),
+        builtin(
+            "array",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                Ok(Expression::Array(Rc::new(RefCell::new(args.to_vec()))))
+            },
+        ),
+    );
+    data.insert(
+        "make-array".to_string(),
+        builtin(
+            "make-array",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let (len, fill) = match args {
+                    [len] => (parse_single_index(len)?, Expression::Nil),
+                    [len, fill] => (parse_single_index(len)?, fill.clone()),
+                    _ => {
+                        return Err(Error::arity(format!(
+                            "make-array expects 1 or 2 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                let mut items = Vec::new();
+                items.try_reserve_exact(len).map_err(|_| {
+                    Error::arithmetic(format!("make-array: {} elements are too many", len))
+                })?;
+                items.resize(len, fill);
+                Ok(Expression::Array(Rc::new(RefCell::new(items))))
+            },
+        ),
+    );
+    data.insert(
+        "array-length".to_string(),
+        builtin(
+            "array-length",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let items = parse_only_arg(args).and_then(parse_single_array)?;
+                let len = items.borrow().len();
+                Ok(Expression::Number(Number::from(len)))
+            },
+        ),
+    );
+    data.insert(
+        "array-ref".to_string(),
+        builtin(
+            "array-ref",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                match args {
+                    [items, idx] => {
+                        let items = parse_single_array(items)?.borrow();
+                        let idx = parse_single_index(idx)?;
+                        items.get(idx).cloned().ok_or(Error::type_error(format!(
+                            "index {} out of bounds for length {}",
+                            idx,
+                            items.len()
+                        )))
+                    }
+                    _ => Err(Error::arity(format!(
+                        "array-ref expects 2 arguments, got {}",
+                        args.len()
+                    ))),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "array-set!".to_string(),
+        builtin(
+            "array-set!",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                match args {
+                    [items, idx, exp] => {
+                        let mut items = parse_single_array(items)?.borrow_mut();
+                        let idx = parse_single_index(idx)?;
+                        let len = items.len();
+                        let slot = items.get_mut(idx).ok_or(Error::type_error(format!(
+                            "index {} out of bounds for length {}",
+                            idx, len
+                        )))?;
+                        *slot = exp.clone();
+                        Ok(Expression::Nil)
+                    }
+                    _ => Err(Error::arity(format!(
+                        "array-set! expects 3 arguments, got {}",
+                        args.len()
+                    ))),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "array-push!".to_string(),
+        builtin(
+            "array-push!",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                match args {
+                    [items, exp] => {
+                        parse_single_array(items)?.borrow_mut().push(exp.clone());
+                        Ok(Expression::Nil)
+                    }
+                    _ => Err(Error::arity(format!(
+                        "array-push! expects 2 arguments, got {}",
+                        args.len()
+                    ))),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "array->list".to_string(),
+        builtin(
+            "array->list",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let items = parse_only_arg(args).and_then(parse_single_array)?;
+                let list = items.borrow().clone();
+                Ok(Expression::List(list.into()))
+            },
+        ),
+    );
+    data.insert(
+        "list->array".to_string(),
+        builtin(
+            "list->array",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let list = parse_only_arg(args).and_then(parse_single_list)?;
+                Ok(Expression::Array(Rc::new(RefCell::new(list))))
+            },
+        ),
+    );
+
+    data.insert(
+        "vector".to_string(),
+        builtin(
+            "vector",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                Ok(Expression::Vector(Rc::new(args.to_vec())))
+            },
+        ),
+    );
+    data.insert(
+        "list->vector".to_string(),
+        builtin(
+            "list->vector",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let list = parse_only_arg(args).and_then(parse_single_list)?;
+                Ok(Expression::Vector(Rc::new(list)))
+            },
+        ),
+    );
+    data.insert(
+        "vector->list".to_string(),
+        builtin(
+            "vector->list",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let list = parse_only_arg(args).and_then(parse_single_list)?;
+                Ok(Expression::List(list.into()))
+            },
+        ),
+    );
+    data.insert(
+        "hash-map".to_string(),
+        builtin(
+            "hash-map",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                if !args.len().is_multiple_of(2) {
+                    return Err(Error::arity("hash-map expects keys and values in pairs"));
+                }
+                let mut map = Map::new();
+                for pair in args.chunks(2) {
+                    map.insert(pair[0].clone(), pair[1].clone())?;
+                }
+                Ok(Expression::Map(Rc::new(map)))
+            },
+        ),
+    );
+    data.insert(
+        "get".to_string(),
+        builtin("get", |args: &[Expression]| -> Result<Expression, Error> {
+            // `(get coll key default)` returns default instead of `nil` for
+            // a missing key.
+            let (coll, key, default) = match args {
+                [coll, key] => (coll, key, Expression::Nil),
+                [coll, key, default] => (coll, key, default.clone()),
+                _ => {
+                    return Err(Error::arity(format!(
+                        "get expects 2 or 3 arguments, got {}",
+                        args.len()
+                    )))
+                }
+            };
+            Ok(lookup(coll, key)?.unwrap_or(default))
+        }),
+    );
+    data.insert(
+        "contains?".to_string(),
+        builtin(
+            "contains?",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                match args {
+                    [coll, key] => Ok(Expression::Bool(lookup(coll, key)?.is_some())),
+                    _ => Err(Error::arity(format!(
+                        "contains? expects 2 arguments, got {}",
+                        args.len()
+                    ))),
+                }
+            },
+        ),
+    );
+    data.insert(
+        "assoc".to_string(),
+        builtin(
+            "assoc",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                // `(assoc coll k1 v1 k2 v2 ...)`
+                let (coll, pairs) = match args.split_first() {
+                    Some((coll, pairs)) if !pairs.is_empty() && pairs.len().is_multiple_of(2) => {
+                        (coll, pairs)
+                    }
+                    _ => {
+                        return Err(Error::arity(
+                            "assoc expects a map or a vector, and keys and values in pairs",
+                        ))
+                    }
+                };
+                pairs.chunks(2).try_fold(coll.clone(), |coll, pair| {
+                    assoc(&coll, pair[0].clone(), pair[1].clone())
+                })
+            },
+        ),
+    );
+    data.insert(
+        "dissoc".to_string(),
+        builtin(
+            "dissoc",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                let (map, keys) = match args.split_first() {
+                    Some((map, keys)) => (parse_single_map(map)?, keys),
+                    None => return Err(Error::arity("dissoc expects a map and keys")),
+                };
+                let mut map = map.as_ref().clone();
+                for key in keys {
+                    map.remove(key);
+                }
+                Ok(Expression::Map(Rc::new(map)))
+            },
+        ),
+    );
+    data.insert(
+        "keys".to_string(),
+        builtin("keys", |args: &[Expression]| -> Result<Expression, Error> {
+            let map = parse_only_arg(args).and_then(parse_single_map)?;
+            Ok(Expression::List(
+                map.iter().map(|(k, _)| k.clone()).collect(),
+            ))
+        }),
+    );
+    data.insert(
+        "vals".to_string(),
+        builtin("vals", |args: &[Expression]| -> Result<Expression, Error> {
+            let map = parse_only_arg(args).and_then(parse_single_map)?;
+            Ok(Expression::List(
+                map.iter().map(|(_, v)| v.clone()).collect(),
+            ))
+        }),
+    );
+    data.insert(
+        "update".to_string(),
+        builtin(
+            "update",
+            |args: &[Expression]| -> Result<Expression, Error> {
+                // `(update coll key f x ...)` sets key to `(f old-value x ...)`.
+                let (coll, key, f, f_args) = match args {
+                    [coll, key, f, f_args @ ..] => (coll, key, f, f_args),
+                    _ => {
+                        return Err(Error::arity(
+                            "update expects a map or a vector, a key and a function",
+                        ))
+                    }
+                };
+                let mut all_args = vec![lookup(coll, key)?.unwrap_or(Expression::Nil)];
+                all_args.extend_from_slice(f_args);
+                assoc(coll, key.clone(), apply(f, &all_args)?)
+            },
+        ),
+    );
+
+    data
+}
+
+// The arguments of `print`, `println` and `error`, separated by spaces.
+fn display_args(args: &[Expression]) -> String {
+    let strs: Vec<String> = args.iter().map(Expression::display).collect();
+    strs.join(" ")
+}
+
+// Output is flushed on every write, so text without a trailing newline
+// shows up before a prompt or a long computation.
+fn write_output(out: &mut impl Write, s: &str) -> Result<Expression, Error> {
+    out.write_all(s.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| Error::io("cannot write output", &e))?;
+    Ok(Expression::Nil)
+}
+
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+// Turns the result of a numeric builtin into a value, reporting NaN and
+// infinities as errors instead of letting them leak into the program.
+pub(super) fn number_result(name: &str, n: Number) -> Result<Expression, Error> {
+    match n {
+        Number::Float(x) if x.is_nan() => Err(Error::arithmetic(format!(
+            "{}: argument out of domain",
+            name
+        ))),
+        Number::Float(x) if x.is_infinite() => {
+            Err(Error::arithmetic(format!("{}: result out of range", name)))
+        }
+        _ => Ok(Expression::Number(n)),
+    }
+}
+
+// `min` and `max` return an inexact result if any argument is inexact.
+fn inexact_if_any(n: &Number, numbers: &[Number]) -> Number {
+    if numbers.iter().all(Number::is_exact) {
+        n.clone()
+    } else {
+        n.to_inexact()
+    }
+}
+
+fn parse_only_arg(args: &[Expression]) -> Result<&Expression, Error> {
+    match args {
+        [exp] => Ok(exp),
+        _ => Err(Error::arity(format!(
+            "expected 1 argument, got {}",
+            args.len()
+        ))),
+    }
+}
+
+fn parse_list_of_numbers(args: &[Expression]) -> Result<Vec<Number>, Error> {
+    args.iter().map(parse_single_number).collect()
+}
+
+fn parse_single_number(exp: &Expression) -> Result<Number, Error> {
+    match exp {
+        Expression::Number(num) => Ok(num.clone()),
+        _ => Err(Error::type_error(format!("expected a number, got {}", exp))),
+    }
+}
+
+fn parse_list_of_floats(args: &[Expression]) -> Result<Vec<f64>, Error> {
+    args.iter().map(parse_single_float).collect()
+}
+
+fn parse_single_float(exp: &Expression) -> Result<f64, Error> {
+    parse_single_number(exp).map(|num| num.to_f64())
+}
+
+fn parse_single_index(exp: &Expression) -> Result<usize, Error> {
+    let num = parse_single_number(exp)?;
+    num.to_usize().ok_or(Error::type_error(format!(
+        "expected a non-negative exact integer, got {}",
+        num
+    )))
+}
+
+// `nil` stands in for the empty list wherever a list is expected, and a
+// vector for a list of its items.
+pub(super) fn parse_single_list(exp: &Expression) -> Result<Vec<Expression>, Error> {
+    match exp {
+        Expression::List(list) => Ok(list.to_vec()),
+        Expression::Nil => Ok(vec![]),
+        Expression::Vector(items) => Ok(items.to_vec()),
+        _ => Err(Error::type_error(format!("expected a list, got {}", exp))),
+    }
+}
+
+fn parse_single_map(exp: &Expression) -> Result<&Rc<Map>, Error> {
+    match exp {
+        Expression::Map(map) => Ok(map),
+        _ => Err(Error::type_error(format!("expected a map, got {}", exp))),
+    }
+}
+
+// Looks `key` up in a map, or an index in a vector; `nil` has no entries.
+fn lookup(coll: &Expression, key: &Expression) -> Result<Option<Expression>, Error> {
+    match coll {
+        Expression::Map(map) => Ok(map.get(key).cloned()),
+        Expression::Vector(items) => Ok(parse_single_index(key)
+            .ok()
+            .and_then(|idx| items.get(idx).cloned())),
+        Expression::Nil => Ok(None),
+        _ => Err(Error::type_error(format!(
+            "expected a map or a vector, got {}",
+            coll
+        ))),
+    }
+}
+
+// A copy of the map or vector with `key` set to `value`. A vector can also
+// grow by one item at its end.
+fn assoc(coll: &Expression, key: Expression, value: Expression) -> Result<Expression, Error> {
+    match coll {
+        Expression::Map(map) => {
+            let mut map = map.as_ref().clone();
+            map.insert(key, value)?;
+            Ok(Expression::Map(Rc::new(map)))
+        }
+        Expression::Nil => {
+            let mut map = Map::new();
+            map.insert(key, value)?;
+            Ok(Expression::Map(Rc::new(map)))
+        }
+        Expression::Vector(items) => {
+            let idx = parse_single_index(&key)?;
+            let mut items = items.to_vec();
+            match idx.cmp(&items.len()) {
+                Ordering::Less => items[idx] = value,
+                Ordering::Equal => items.push(value),
+                Ordering::Greater => {
+                    return Err(Error::type_error(format!(
+                        "index {} out of bounds for length {}",
+                        idx,
+                        items.len()
+                    )))
+                }
+            }
+            Ok(Expression::Vector(Rc::new(items)))
+        }
+        _ => Err(Error::type_error(format!(
+            "expected a map or a vector, got {}",
+            coll
+        ))),
+    }
+}
+
+fn parse_single_box(exp: &Expression) -> Result<&Rc<RefCell<Expression>>, Error> {
+    match exp {
+        Expression::Box(cell) => Ok(cell),
+        _ => Err(Error::type_error(format!("expected a box, got {}", exp))),
+    }
+}
+
+fn parse_single_array(exp: &Expression) -> Result<&Rc<RefCell<Vec<Expression>>>, Error> {
+    match exp {
+        Expression::Array(items) => Ok(items),
+        _ => Err(Error::type_error(format!("expected an array, got {}", exp))),
+    }
+}
+
+fn parse_single_error(exp: &Expression) -> Result<&Rc<Error>, Error> {
+    match exp {
+        Expression::Error(e) => Ok(e),
+        _ => Err(Error::type_error(format!("expected an error, got {}", exp))),
+    }
+}
+
+fn parse_list_of_strings(args: &[Expression]) -> Result<Vec<String>, Error> {
+    args.iter().map(parse_single_string).collect()
+}
+
+fn parse_single_string(exp: &Expression) -> Result<String, Error> {
+    match exp {
+        Expression::Str(s) => Ok(s.clone()),
+        _ => Err(Error::type_error(format!("expected a string, got {}", exp))),
+    }
+}
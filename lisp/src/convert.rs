@@ -0,0 +1,212 @@
+use super::builtins::number_result;
+use super::number::Number;
+use super::{Error, Expression, Function};
+
+/// Rust types that lisp values convert to when they are passed to a function
+/// registered with [`Interpreter::define_fn`](super::Interpreter::define_fn).
+pub trait FromExpression: Sized {
+    fn from_expression(exp: &Expression) -> Result<Self, Error>;
+}
+
+impl FromExpression for Expression {
+    fn from_expression(exp: &Expression) -> Result<Expression, Error> {
+        Ok(exp.clone())
+    }
+}
+
+impl FromExpression for f64 {
+    fn from_expression(exp: &Expression) -> Result<f64, Error> {
+        match exp {
+            Expression::Number(n) => Ok(n.to_f64()),
+            _ => Err(Error::type_error(format!("expected a number, got {}", exp))),
+        }
+    }
+}
+
+impl FromExpression for i64 {
+    fn from_expression(exp: &Expression) -> Result<i64, Error> {
+        match exp {
+            Expression::Number(Number::Int(n)) => Ok(*n),
+            _ => Err(Error::type_error(format!(
+                "expected an integer that fits in 64 bits, got {}",
+                exp
+            ))),
+        }
+    }
+}
+
+impl FromExpression for bool {
+    fn from_expression(exp: &Expression) -> Result<bool, Error> {
+        match exp {
+            Expression::Bool(b) => Ok(*b),
+            _ => Err(Error::type_error(format!(
+                "expected a boolean, got {}",
+                exp
+            ))),
+        }
+    }
+}
+
+impl FromExpression for String {
+    fn from_expression(exp: &Expression) -> Result<String, Error> {
+        match exp {
+            Expression::Str(s) => Ok(s.clone()),
+            _ => Err(Error::type_error(format!("expected a string, got {}", exp))),
+        }
+    }
+}
+
+impl<T: FromExpression> FromExpression for Vec<T> {
+    fn from_expression(exp: &Expression) -> Result<Vec<T>, Error> {
+        match exp {
+            Expression::List(list) => list.iter().map(T::from_expression).collect(),
+            Expression::Vector(items) => items.iter().map(T::from_expression).collect(),
+            Expression::Nil => Ok(vec![]),
+            _ => Err(Error::type_error(format!("expected a list, got {}", exp))),
+        }
+    }
+}
+
+/// `nil` converts to `None`.
+impl<T: FromExpression> FromExpression for Option<T> {
+    fn from_expression(exp: &Expression) -> Result<Option<T>, Error> {
+        match exp {
+            Expression::Nil => Ok(None),
+            _ => T::from_expression(exp).map(Some),
+        }
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(x: f64) -> Expression {
+        Expression::Number(Number::Float(x))
+    }
+}
+
+impl From<i64> for Expression {
+    fn from(n: i64) -> Expression {
+        Expression::Number(Number::Int(n))
+    }
+}
+
+impl From<Number> for Expression {
+    fn from(n: Number) -> Expression {
+        Expression::Number(n)
+    }
+}
+
+impl From<bool> for Expression {
+    fn from(b: bool) -> Expression {
+        Expression::Bool(b)
+    }
+}
+
+impl From<String> for Expression {
+    fn from(s: String) -> Expression {
+        Expression::Str(s)
+    }
+}
+
+impl From<&str> for Expression {
+    fn from(s: &str) -> Expression {
+        Expression::Str(s.to_string())
+    }
+}
+
+impl From<()> for Expression {
+    fn from(_: ()) -> Expression {
+        Expression::Nil
+    }
+}
+
+impl<T: Into<Expression>> From<Vec<T>> for Expression {
+    fn from(xs: Vec<T>) -> Expression {
+        Expression::List(xs.into_iter().map(Into::into).collect())
+    }
+}
+
+/// `None` converts to `nil`.
+impl<T: Into<Expression>> From<Option<T>> for Expression {
+    fn from(x: Option<T>) -> Expression {
+        x.map_or(Expression::Nil, Into::into)
+    }
+}
+
+/// What a function registered with
+/// [`Interpreter::define_fn`](super::Interpreter::define_fn) may return: a
+/// value that converts to a lisp value, or a `Result` of one, whose error is
+/// raised in the lisp program.
+pub trait IntoResult {
+    fn into_result(self) -> Result<Expression, Error>;
+}
+
+impl<T: Into<Expression>> IntoResult for T {
+    fn into_result(self) -> Result<Expression, Error> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Expression>> IntoResult for Result<T, Error> {
+    fn into_result(self) -> Result<Expression, Error> {
+        self.map(Into::into)
+    }
+}
+
+/// Rust functions and closures that can be registered with
+/// [`Interpreter::define_fn`](super::Interpreter::define_fn). `Args` is the
+/// tuple of their parameter types; it only serves to tell the implementations
+/// for different numbers of parameters apart.
+pub trait NativeFn<Args> {
+    fn into_function(self, name: &str) -> Function;
+}
+
+macro_rules! native_fn {
+    ($arity:expr; $($arg:ident $var:ident),*) => {
+        impl<F, R, $($arg),*> NativeFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoResult,
+            $($arg: FromExpression),*
+        {
+            fn into_function(self, name: &str) -> Function {
+                let fn_name = name.to_string();
+                Function::new(name, move |args: &[Expression]| match args {
+                    [$($var),*] => {
+                        let result = self($($arg::from_expression($var)?),*).into_result()?;
+                        finite_result(&fn_name, result)
+                    }
+                    _ => Err(Error::arity(format!(
+                        "{} expects {} arguments, got {}",
+                        fn_name,
+                        $arity,
+                        args.len()
+                    ))),
+                })
+            }
+        }
+    };
+}
+
+// Lisp numbers are always finite, so a NaN or an infinity that a Rust
+// function returns, on its own or in a list, is raised as the same arithmetic
+// error the numeric builtins raise.
+fn finite_result(name: &str, result: Expression) -> Result<Expression, Error> {
+    let mut todo = vec![result.clone()];
+    while let Some(exp) = todo.pop() {
+        match exp {
+            Expression::Number(n) => {
+                number_result(name, n)?;
+            }
+            Expression::List(items) => todo.extend(items.iter().cloned()),
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+native_fn!(0;);
+native_fn!(1; A a);
+native_fn!(2; A a, B b);
+native_fn!(3; A a, B b, C c);
+native_fn!(4; A a, B b, C c, D d);
+native_fn!(5; A a, B b, C c, D d, E e);
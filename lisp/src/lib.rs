@@ -0,0 +1,2255 @@
+/*
+MIT License
+
+Copyright (c) Stepan Parunashvili
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! A small lisp interpreter. [`Interpreter`] is the entry point for embedding
+//! it: it evaluates source text and files, and lets the host define and look
+//! up globals and call lisp functions with [`Expression`] arguments.
+
+mod builtins;
+mod convert;
+mod map;
+mod number;
+mod syntax_rules;
+
+use builtins::parse_single_list;
+pub use convert::{FromExpression, IntoResult, NativeFn};
+pub use map::Map;
+pub use number::Number;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::iter::{FromIterator, Peekable};
+use std::ops::Deref;
+use std::path::Path;
+use std::rc::{Rc, Weak};
+use std::str::Chars;
+use std::sync::atomic;
+use std::sync::atomic::AtomicUsize;
+use syntax_rules::{Alias, SyntaxRules};
+
+// Compiles and runs the example in the README along with the doc tests.
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;
+
+/// A lisp value. Code is data, so this is also what the reader produces and
+/// the evaluator consumes.
+#[derive(Clone)]
+pub enum Expression {
+    Nil,
+    Bool(bool),
+    Symbol(String),
+    // `:name`, which evaluates to itself; names keyword arguments.
+    Keyword(String),
+    Number(Number),
+    Str(String),
+    List(List),
+    // `[1 2 3]`, an immutable vector.
+    Vector(Rc<Vec<Expression>>),
+    Map(Rc<Map>),
+    // `{k v ...}` as the reader reads it, keys and values alternating. The
+    // keys are still forms, so whether they all differ is only known once it
+    // is evaluated, or quoted, into a `Map`.
+    MapLiteral(Rc<Vec<Expression>>),
+    Function(Function),
+    Lambda(Lambda),
+    Macro(Lambda),
+    Syntax(Rc<SyntaxRules>),
+    // A name in the expansion of a `syntax-rules` macro, which refers to the
+    // binding where the macro was defined.
+    Alias(Rc<Alias>),
+    Box(Rc<RefCell<Expression>>),
+    Array(Rc<RefCell<Vec<Expression>>>),
+    // A caught error, as bound by `catch`.
+    Error(Rc<Error>),
+}
+
+/// The items of a list. Copies of a list share them, so passing forms and
+/// values around never copies the items themselves.
+#[derive(Clone)]
+pub struct List {
+    data: Rc<ListData>,
+}
+
+struct ListData {
+    items: Vec<Expression>,
+    // Where the reader found the list, so errors can point at the innermost
+    // form that failed. Lists built at run time have none.
+    span: Option<Span>,
+}
+
+// Lists nested in a list that goes away with it are taken apart here, one
+// after the other, rather than each dropping the next: a list built at run
+// time can nest far more deeply than the stack allows.
+impl Drop for ListData {
+    fn drop(&mut self) {
+        let mut todo = std::mem::take(&mut self.items);
+        while let Some(exp) = todo.pop() {
+            if let Expression::List(list) = exp {
+                if let Ok(mut data) = Rc::try_unwrap(list.data) {
+                    todo.append(&mut data.items);
+                }
+            }
+        }
+    }
+}
+
+impl List {
+    fn read(items: Vec<Expression>, span: Span) -> List {
+        List {
+            data: Rc::new(ListData {
+                items,
+                span: Some(span),
+            }),
+        }
+    }
+
+    pub fn as_slice(&self) -> &[Expression] {
+        &self.data.items
+    }
+
+    fn span(&self) -> Option<Span> {
+        self.data.span
+    }
+}
+
+impl Deref for List {
+    type Target = [Expression];
+
+    fn deref(&self) -> &[Expression] {
+        &self.data.items
+    }
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &List) -> bool {
+        self.data.items == other.data.items
+    }
+}
+
+impl From<Vec<Expression>> for List {
+    fn from(items: Vec<Expression>) -> List {
+        List {
+            data: Rc::new(ListData { items, span: None }),
+        }
+    }
+}
+
+impl FromIterator<Expression> for List {
+    fn from_iter<I: IntoIterator<Item = Expression>>(iter: I) -> List {
+        List::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = &'a Expression;
+    type IntoIter = std::slice::Iter<'a, Expression>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.items.iter()
+    }
+}
+
+/// A function implemented in Rust. It can capture state, such as a handle
+/// owned by the host program.
+#[derive(Clone)]
+pub struct Function {
+    name: Rc<str>,
+    f: Rc<NativeClosure>,
+}
+
+type NativeClosure = dyn Fn(&[Expression]) -> Result<Expression, Error>;
+
+impl Function {
+    /// Wraps a closure that takes the evaluated arguments as they are. See
+    /// [`Interpreter::define_fn`] for one that converts them from and to Rust
+    /// types.
+    pub fn new(
+        name: &str,
+        f: impl Fn(&[Expression]) -> Result<Expression, Error> + 'static,
+    ) -> Function {
+        Function {
+            name: Rc::from(name),
+            f: Rc::new(f),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn call(&self, args: &[Expression]) -> Result<Expression, Error> {
+        (self.f)(args)
+    }
+}
+
+/// The parameters of a `fn`, as read by `parse_params`.
+#[derive(Default)]
+struct Params {
+    required: Vec<String>,
+    // Names and default forms.
+    optional: Vec<(String, Expression)>,
+    rest: Option<String>,
+    keys: Vec<(String, Expression)>,
+}
+
+/// A closure created by `fn`, or the expander of a `defmacro`.
+#[derive(Clone)]
+pub struct Lambda {
+    // The name it was first defined under, for printing.
+    name: Option<Rc<str>>,
+    params: Rc<Params>,
+    body: Rc<Vec<Expression>>,
+    env: Captured,
+}
+
+// The environment a lambda closes over. A lambda bound in the very frame it
+// closes over, as with `letrec` or a named `let`, holds that frame weakly so
+// the two don't keep each other alive; `env_get` hands out a strong copy,
+// which is alive for as long as anything can still look the binding up.
+#[derive(Clone)]
+enum Captured {
+    Strong(Rc<RefCell<Environment>>),
+    Weak(Weak<RefCell<Environment>>),
+}
+
+impl Lambda {
+    // Gives an anonymous lambda the name it is being defined under.
+    fn named(self, name: &str) -> Lambda {
+        match self.name {
+            Some(_) => self,
+            None => Lambda {
+                name: Some(Rc::from(name)),
+                ..self
+            },
+        }
+    }
+
+    fn env(&self) -> Result<Rc<RefCell<Environment>>, Error> {
+        match &self.env {
+            Captured::Strong(env) => Ok(env.clone()),
+            Captured::Weak(env) => env
+                .upgrade()
+                .ok_or_else(|| Error::type_error("the environment of this fn no longer exists")),
+        }
+    }
+
+    fn env_ptr(&self) -> *const RefCell<Environment> {
+        match &self.env {
+            Captured::Strong(env) => Rc::as_ptr(env),
+            Captured::Weak(env) => env.as_ptr(),
+        }
+    }
+}
+
+impl Expression {
+    /// The human-friendly rendering that `display` and `print` use. It is
+    /// the same as the printer's, except that strings appear as their bare
+    /// contents, so it doesn't always read back.
+    pub fn display(&self) -> String {
+        Printed {
+            exp: self,
+            readable: false,
+            zero_unsigned: false,
+        }
+        .to_string()
+    }
+
+    // The printed form, except that `-0.0` is written as `0.0`, so that two
+    // values that are `equal?` write the same.
+    pub(crate) fn key_string(&self) -> String {
+        Printed {
+            exp: self,
+            readable: true,
+            zero_unsigned: true,
+        }
+        .to_string()
+    }
+}
+
+// The printer: the rendering of data reads back as an equal value.
+// Procedures, macros and mutable objects have no literal syntax and are
+// shown as `#<...>`.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printed = Printed {
+            exp: self,
+            readable: true,
+            zero_unsigned: false,
+        };
+        write!(f, "{}", printed)
+    }
+}
+
+struct Printed<'a> {
+    exp: &'a Expression,
+    // Whether strings are written as literals.
+    readable: bool,
+    // Whether `-0.0` is written as `0.0`.
+    zero_unsigned: bool,
+}
+
+// What is left to write of a value being printed.
+enum Piece {
+    Exp(Expression),
+    Text(&'static str),
+    // The `>` that closes the innermost box or array being written.
+    Leave,
+}
+
+impl Printed<'_> {
+    // Writes `exp` whole if it is an atom. Otherwise writes its opening and
+    // pushes the rest of it onto `todo`, last piece first. `open` has the
+    // addresses of the boxes and arrays `exp` is inside of: one that contains
+    // itself is written as `#<box ...>` the second time round.
+    fn write_exp(
+        &self,
+        f: &mut fmt::Formatter,
+        exp: &Expression,
+        todo: &mut Vec<Piece>,
+        open: &mut Vec<usize>,
+    ) -> fmt::Result {
+        match exp {
+            Expression::Nil => write!(f, "nil"),
+            Expression::Bool(a) => write!(f, "{}", a),
+            Expression::Symbol(s) => write!(f, "{}", s),
+            Expression::Keyword(s) => write!(f, ":{}", s),
+            Expression::Number(Number::Float(x)) if self.zero_unsigned && *x == 0.0 => {
+                write!(f, "0.0")
+            }
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Str(s) if self.readable => write_string_literal(f, s),
+            Expression::Str(s) => write!(f, "{}", s),
+            Expression::List(list) => {
+                push_seq(todo, list.to_vec(), Piece::Text(")"));
+                write!(f, "(")
+            }
+            Expression::Vector(items) => {
+                push_seq(todo, items.to_vec(), Piece::Text("]"));
+                write!(f, "[")
+            }
+            Expression::Map(map) => {
+                let entries: Vec<Expression> = map
+                    .iter()
+                    .flat_map(|(k, v)| vec![k.clone(), v.clone()])
+                    .collect();
+                push_seq(todo, entries, Piece::Text("}"));
+                write!(f, "{{")
+            }
+            Expression::MapLiteral(items) => {
+                push_seq(todo, items.to_vec(), Piece::Text("}"));
+                write!(f, "{{")
+            }
+            Expression::Function(func) => write!(f, "#<builtin {}>", func.name),
+            Expression::Lambda(lambda) => write_lambda(f, "fn", lambda),
+            Expression::Macro(lambda) => write_lambda(f, "macro", lambda),
+            Expression::Syntax(_) => write!(f, "#<syntax-rules>"),
+            Expression::Alias(alias) => write!(f, "{}", alias.name),
+            Expression::Box(cell) => {
+                let address = Rc::as_ptr(cell) as usize;
+                if open.contains(&address) {
+                    return write!(f, "#<box ...>");
+                }
+                open.push(address);
+                push_seq(todo, vec![cell.borrow().clone()], Piece::Leave);
+                write!(f, "#<box ")
+            }
+            Expression::Array(items) => {
+                let address = Rc::as_ptr(items) as usize;
+                if open.contains(&address) {
+                    return write!(f, "#<array ...>");
+                }
+                open.push(address);
+                let items = items.borrow().to_vec();
+                let empty = items.is_empty();
+                push_seq(todo, items, Piece::Leave);
+                write!(f, "{}", if empty { "#<array" } else { "#<array " })
+            }
+            Expression::Error(e) => write!(f, "#<error {}>", e),
+        }
+    }
+}
+
+// Pushes `xs`, separated by spaces, and then `close` onto `todo`.
+fn push_seq(todo: &mut Vec<Piece>, xs: Vec<Expression>, close: Piece) {
+    todo.push(close);
+    for (i, x) in xs.into_iter().enumerate().rev() {
+        todo.push(Piece::Exp(x));
+        if i > 0 {
+            todo.push(Piece::Text(" "));
+        }
+    }
+}
+
+// Values are printed from an explicit stack of what is left to write rather
+// than by recursion, so that however deeply they nest, they print without
+// overflowing the stack.
+impl fmt::Display for Printed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut todo = vec![];
+        let mut open = vec![];
+        self.write_exp(f, self.exp, &mut todo, &mut open)?;
+        while let Some(piece) = todo.pop() {
+            match piece {
+                Piece::Exp(exp) => self.write_exp(f, &exp, &mut todo, &mut open)?,
+                Piece::Text(s) => f.write_str(s)?,
+                Piece::Leave => {
+                    open.pop();
+                    f.write_str(">")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Writes `s` with the escapes the reader understands.
+fn write_string_literal(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            '\0' => write!(f, "\\0")?,
+            _ => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// `#<fn name (a (b 1) & rest)>`, leaving out the name of an anonymous `fn`.
+fn write_lambda(f: &mut fmt::Formatter, kind: &str, lambda: &Lambda) -> fmt::Result {
+    write!(f, "#<{} ", kind)?;
+    if let Some(name) = &lambda.name {
+        write!(f, "{} ", name)?;
+    }
+    let params = &lambda.params;
+    let mut xs: Vec<String> = params.required.clone();
+    for (k, default) in &params.optional {
+        xs.push(format!("({} {})", k, default));
+    }
+    if let Some(rest) = &params.rest {
+        xs.push(format!("& {}", rest));
+    }
+    if !params.keys.is_empty() {
+        xs.push("&key".to_string());
+    }
+    for (k, default) in &params.keys {
+        match default {
+            Expression::Nil => xs.push(k.clone()),
+            _ => xs.push(format!("({} {})", k, default)),
+        }
+    }
+    write!(f, "({})>", xs.join(" "))
+}
+
+// Structural equality, as `equal?` sees it: lists, arrays and boxes compare
+// their contents, numbers compare equal only if they are also equally exact,
+// and procedures and macros only equal themselves.
+//
+// Like printing, comparison works from an explicit stack of the pairs left to
+// compare rather than by recursion, so it can't overflow the stack. Boxes and
+// arrays that contain themselves are equal if nothing else tells them apart.
+impl PartialEq for Expression {
+    fn eq(&self, other: &Expression) -> bool {
+        let mut todo = vec![];
+        let mut seen = HashSet::new();
+        if !shallow_eq(self, other, &mut todo, &mut seen) {
+            return false;
+        }
+        while let Some((a, b)) = todo.pop() {
+            if !shallow_eq(&a, &b, &mut todo, &mut seen) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Whether `a` and `b` are equal apart from their items, which it pushes onto
+// `todo` in pairs to be compared in turn. `seen` has the addresses of the
+// pairs of boxes and arrays pushed so far, which are only pushed once.
+fn shallow_eq(
+    a: &Expression,
+    b: &Expression,
+    todo: &mut Vec<(Expression, Expression)>,
+    seen: &mut HashSet<(usize, usize)>,
+) -> bool {
+    match (a, b) {
+        (Expression::Nil, Expression::Nil) => true,
+        (Expression::Bool(a), Expression::Bool(b)) => a == b,
+        (Expression::Symbol(a), Expression::Symbol(b)) => a == b,
+        (Expression::Keyword(a), Expression::Keyword(b)) => a == b,
+        (Expression::Number(a), Expression::Number(b)) => a.is_exact() == b.is_exact() && a == b,
+        (Expression::Str(a), Expression::Str(b)) => a == b,
+        (Expression::List(a), Expression::List(b)) => push_pairs(todo, a, b),
+        (Expression::Vector(a), Expression::Vector(b)) => push_pairs(todo, a, b),
+        (Expression::MapLiteral(a), Expression::MapLiteral(b)) => push_pairs(todo, a, b),
+        (Expression::Map(a), Expression::Map(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(k, v)| match b.get(k) {
+                    Some(w) => {
+                        todo.push((v.clone(), w.clone()));
+                        true
+                    }
+                    None => false,
+                })
+        }
+        (Expression::Function(a), Expression::Function(b)) => Rc::ptr_eq(&a.f, &b.f),
+        (Expression::Lambda(a), Expression::Lambda(b))
+        | (Expression::Macro(a), Expression::Macro(b)) => {
+            Rc::ptr_eq(&a.body, &b.body) && a.env_ptr() == b.env_ptr()
+        }
+        (Expression::Syntax(a), Expression::Syntax(b)) => Rc::ptr_eq(a, b),
+        (Expression::Alias(a), Expression::Alias(b)) => {
+            a.name == b.name && a.env_ptr() == b.env_ptr()
+        }
+        (Expression::Box(a), Expression::Box(b)) => {
+            if !Rc::ptr_eq(a, b) && seen.insert((Rc::as_ptr(a) as usize, Rc::as_ptr(b) as usize)) {
+                todo.push((a.borrow().clone(), b.borrow().clone()));
+            }
+            true
+        }
+        (Expression::Array(a), Expression::Array(b)) => {
+            Rc::ptr_eq(a, b)
+                || !seen.insert((Rc::as_ptr(a) as usize, Rc::as_ptr(b) as usize))
+                || push_pairs(todo, &a.borrow(), &b.borrow())
+        }
+        (Expression::Error(a), Expression::Error(b)) => Rc::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+// Pushes the items of `a` and `b` onto `todo` in pairs, unless there are not
+// as many of one as of the other.
+fn push_pairs(
+    todo: &mut Vec<(Expression, Expression)>,
+    a: &[Expression],
+    b: &[Expression],
+) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    todo.extend(a.iter().cloned().zip(b.iter().cloned()));
+    true
+}
+
+// Identity, as `eq?` sees it: boxes and arrays are the same only if they are
+// the same object, so mutating one is visible through the other. Atoms and
+// the immutable aggregates (lists, vectors and maps) are plain values here and
+// have no identity beyond their contents.
+fn is_eq(a: &Expression, b: &Expression) -> bool {
+    match (a, b) {
+        (Expression::Box(a), Expression::Box(b)) => Rc::ptr_eq(a, b),
+        (Expression::Array(a), Expression::Array(b)) => Rc::ptr_eq(a, b),
+        _ => a == b,
+    }
+}
+
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// A 1-based line and column in the source text, counted in characters.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+/// The source range a token or form was read from; `end` is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Span {
+    start: Position,
+    end: Position,
+    // Tells apart the texts spans were read from, since a function defined
+    // by one call to `parse_eval` can fail in another.
+    source: usize,
+}
+
+impl Span {
+    fn contains(&self, other: &Span) -> bool {
+        self.source == other.source && self.start <= other.start && other.end <= self.end
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ErrorKind {
+    UnboundSymbol(String),
+    Arity(String),
+    Type(String),
+    Parse(String),
+    // A parse error caused by running out of input, e.g. an unclosed `(`.
+    Incomplete(String),
+    Arithmetic(String),
+    // A failed file or console operation.
+    Io(String),
+    User(String),
+    // A value raised with `throw`.
+    Thrown(Expression),
+    // Reading or evaluation went past the stack limit, see `set_stack_limit`.
+    StackOverflow,
+}
+
+/// An error raised while reading or evaluating a program, or thrown by it.
+#[derive(Clone, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    span: Option<Span>,
+}
+
+impl Error {
+    fn unbound_symbol(name: &str) -> Error {
+        Error {
+            kind: ErrorKind::UnboundSymbol(name.to_string()),
+            span: None,
+        }
+    }
+
+    fn arity(msg: impl Into<String>) -> Error {
+        Error {
+            kind: ErrorKind::Arity(msg.into()),
+            span: None,
+        }
+    }
+
+    fn type_error(msg: impl Into<String>) -> Error {
+        Error {
+            kind: ErrorKind::Type(msg.into()),
+            span: None,
+        }
+    }
+
+    fn parse(msg: impl Into<String>, span: Option<Span>) -> Error {
+        Error {
+            kind: ErrorKind::Parse(msg.into()),
+            span,
+        }
+    }
+
+    fn incomplete(msg: impl Into<String>, span: Option<Span>) -> Error {
+        Error {
+            kind: ErrorKind::Incomplete(msg.into()),
+            span,
+        }
+    }
+
+    fn arithmetic(msg: impl Into<String>) -> Error {
+        Error {
+            kind: ErrorKind::Arithmetic(msg.into()),
+            span: None,
+        }
+    }
+
+    fn io(context: &str, e: &io::Error) -> Error {
+        Error {
+            kind: ErrorKind::Io(format!("{}: {}", context, e)),
+            span: None,
+        }
+    }
+
+    /// An error like the ones the `error` builtin raises, for native
+    /// functions to report failures with.
+    pub fn user(msg: impl Into<String>) -> Error {
+        Error {
+            kind: ErrorKind::User(msg.into()),
+            span: None,
+        }
+    }
+
+    fn thrown(exp: Expression) -> Error {
+        Error {
+            kind: ErrorKind::Thrown(exp),
+            span: None,
+        }
+    }
+
+    fn stack_overflow() -> Error {
+        Error {
+            kind: ErrorKind::StackOverflow,
+            span: None,
+        }
+    }
+
+    /// The kind of error as a name such as `type` or `arity`, as the
+    /// `error-kind` builtin reports it.
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            ErrorKind::UnboundSymbol(_) => "unbound-symbol",
+            ErrorKind::Arity(_) => "arity",
+            ErrorKind::Type(_) => "type",
+            ErrorKind::Parse(_) | ErrorKind::Incomplete(_) => "parse",
+            ErrorKind::Arithmetic(_) => "arithmetic",
+            ErrorKind::Io(_) => "io",
+            ErrorKind::User(_) => "user",
+            ErrorKind::Thrown(_) => "thrown",
+            ErrorKind::StackOverflow => "stack-overflow",
+        }
+    }
+
+    /// The message without the kind prefix that `Display` adds.
+    pub fn message(&self) -> String {
+        match &self.kind {
+            ErrorKind::UnboundSymbol(name) => format!("unbound symbol `{}`", name),
+            ErrorKind::Arity(msg)
+            | ErrorKind::Type(msg)
+            | ErrorKind::Parse(msg)
+            | ErrorKind::Incomplete(msg)
+            | ErrorKind::Arithmetic(msg)
+            | ErrorKind::Io(msg)
+            | ErrorKind::User(msg) => msg.clone(),
+            ErrorKind::Thrown(exp) => exp.display(),
+            ErrorKind::StackOverflow => "evaluation nested too deeply".to_string(),
+        }
+    }
+
+    // Attaches `span` unless a more precise location is already known.
+    fn or_span(mut self, span: Option<Span>) -> Error {
+        if self.span.is_none() {
+            self.span = span;
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::UnboundSymbol(name) => write!(f, "unbound symbol `{}`", name),
+            ErrorKind::Arity(msg) => write!(f, "arity mismatch: {}", msg),
+            ErrorKind::Type(msg) => write!(f, "type error: {}", msg),
+            ErrorKind::Parse(msg) | ErrorKind::Incomplete(msg) => {
+                write!(f, "parse error: {}", msg)
+            }
+            ErrorKind::Arithmetic(msg) => write!(f, "arithmetic error: {}", msg),
+            ErrorKind::Io(msg) => write!(f, "io error: {}", msg),
+            ErrorKind::User(msg) => write!(f, "error: {}", msg),
+            ErrorKind::Thrown(exp) => write!(f, "uncaught throw: {}", exp),
+            ErrorKind::StackOverflow => write!(f, "stack overflow: {}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Renders the error followed by the offending source line with carets under
+/// the span, e.g.
+///
+///   type error: expected a number, got "a"
+///    --> repl:1:6
+///     |
+///   1 | (* 2 (+ 1 "a"))
+///     |      ^^^^^^^^^
+pub fn render_error(e: &Error, name: &str, source: &str) -> String {
+    let span = match e.span {
+        Some(span) => span,
+        None => return e.to_string(),
+    };
+    let line = source.lines().nth(span.start.line - 1).unwrap_or("");
+    let line_no = span.start.line.to_string();
+    let gutter = " ".repeat(line_no.len());
+    let line_len = line.chars().count() + 1;
+    let end_column = if span.end.line == span.start.line {
+        span.end.column
+    } else {
+        line_len
+    };
+    let carets = "^".repeat(end_column.saturating_sub(span.start.column).max(1));
+    format!(
+        "{}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}",
+        e,
+        gutter,
+        name,
+        span.start.line,
+        span.start.column,
+        gutter,
+        line_no,
+        line,
+        gutter,
+        " ".repeat(span.start.column - 1),
+        carets
+    )
+}
+
+/// The outcome of evaluating a special form or a call: either a finished
+/// value, or a form in tail position that `eval` keeps evaluating in its own
+/// loop instead of recursing, so tail calls run in constant stack.
+enum Step {
+    Done(Expression),
+    Tail(Expression, Rc<RefCell<Environment>>),
+}
+
+#[derive(Clone)]
+struct Environment {
+    data: HashMap<String, Expression>,
+    outer: Option<Rc<RefCell<Environment>>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    // `(`, `[` or `{`, and the matching closing delimiters.
+    Open(char),
+    Close(char),
+    // Reader shorthand such as `'x`, standing for `(quote x)`; holds the name
+    // of the form the next expression gets wrapped in.
+    Prefix(&'static str),
+    Str(String),
+    Atom(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+static SOURCE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// A character stream that keeps track of the current line and column.
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: Position,
+    source: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(expr: &'a str) -> Reader<'a> {
+        Reader {
+            chars: expr.chars().peekable(),
+            position: Position { line: 1, column: 1 },
+            source: SOURCE_COUNTER.fetch_add(1, atomic::Ordering::Relaxed),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+
+    fn span_from(&self, start: Position) -> Span {
+        Span {
+            start,
+            end: self.position,
+            source: self.source,
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut reader = Reader::new(expr);
+    while let Some(c) = reader.peek() {
+        let start = reader.position;
+        let kind = match c {
+            '(' | '[' | '{' => {
+                reader.next();
+                TokenKind::Open(c)
+            }
+            ')' | ']' | '}' => {
+                reader.next();
+                TokenKind::Close(c)
+            }
+            '\'' => {
+                reader.next();
+                TokenKind::Prefix("quote")
+            }
+            '`' => {
+                reader.next();
+                TokenKind::Prefix("quasiquote")
+            }
+            ',' => {
+                reader.next();
+                if reader.peek() == Some('@') {
+                    reader.next();
+                    TokenKind::Prefix("unquote-splicing")
+                } else {
+                    TokenKind::Prefix("unquote")
+                }
+            }
+            '"' => {
+                reader.next();
+                TokenKind::Str(read_string(&mut reader, start)?)
+            }
+            _ if c.is_whitespace() => {
+                reader.next();
+                continue;
+            }
+            ';' => {
+                while reader.peek().is_some_and(|c| c != '\n') {
+                    reader.next();
+                }
+                continue;
+            }
+            _ => {
+                let mut atom = String::new();
+                while let Some(c) = reader.peek() {
+                    if c.is_whitespace() || "()[]{}\";".contains(c) {
+                        break;
+                    }
+                    atom.push(c);
+                    reader.next();
+                }
+                TokenKind::Atom(atom)
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: reader.span_from(start),
+        });
+    }
+
+    Ok(tokens)
+}
+
+// Reads the body of a string literal whose opening `"` at `start` has already been consumed.
+fn read_string(reader: &mut Reader, start: Position) -> Result<String, Error> {
+    let source = reader.source;
+    let unterminated = || {
+        Error::incomplete(
+            "could not find closing `\"`",
+            Some(Span {
+                start,
+                end: start,
+                source,
+            }),
+        )
+    };
+    let mut s = String::new();
+    loop {
+        let escape_start = reader.position;
+        let c = reader.next().ok_or_else(unterminated)?;
+        match c {
+            '"' => return Ok(s),
+            '\\' => {
+                let escaped = reader.next().ok_or_else(unterminated)?;
+                s.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    '\\' => '\\',
+                    '"' => '"',
+                    _ => {
+                        return Err(Error::parse(
+                            format!("unknown escape sequence `\\{}`", escaped),
+                            Some(reader.span_from(escape_start)),
+                        ))
+                    }
+                });
+            }
+            _ => s.push(c),
+        }
+    }
+}
+
+// Reads the single form in `expr` without evaluating it.
+fn read_str(expr: &str) -> Result<Expression, Error> {
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Err(Error::incomplete("expected a form", None));
+    }
+    let (exp, rest) = parse(&tokens)?;
+    match rest.first() {
+        Some(token) => Err(Error::parse("expected a single form", Some(token.span))),
+        None => quoted(&exp),
+    }
+}
+
+fn parse(tokens: &[Token]) -> Result<(Expression, &[Token]), Error> {
+    let (token, rest) = tokens
+        .split_first()
+        .ok_or(Error::parse("could not get token", None))?;
+    let _guard = StackGuard::enter().map_err(|e| e.or_span(Some(token.span)))?;
+    // The span from `token` to the last one the form read took up.
+    let form_span = |rest: &[Token]| Span {
+        end: tokens[tokens.len() - rest.len() - 1].span.end,
+        ..token.span
+    };
+    match &token.kind {
+        TokenKind::Open(open) => {
+            let (items, rest) = read_seq(token, rest)?;
+            let exp = match open {
+                '[' => Expression::Vector(Rc::new(items)),
+                '{' => read_map(token, items)?,
+                _ => Expression::List(List::read(items, form_span(rest))),
+            };
+            Ok((exp, rest))
+        }
+        TokenKind::Close(close) => Err(Error::parse(
+            format!("unexpected `{}`", close),
+            Some(token.span),
+        )),
+        TokenKind::Prefix(name) => {
+            if rest.is_empty() {
+                return Err(Error::incomplete(
+                    format!("{} expects a form", name),
+                    Some(token.span),
+                ));
+            }
+            let (exp, rest) = parse(rest)?;
+            let list = List::read(
+                vec![Expression::Symbol(name.to_string()), exp],
+                form_span(rest),
+            );
+            Ok((Expression::List(list), rest))
+        }
+        TokenKind::Str(s) => Ok((Expression::Str(s.clone()), rest)),
+        TokenKind::Atom(atom) => Ok((parse_atom(atom), rest)),
+    }
+}
+
+fn read_seq<'a>(
+    open: &Token,
+    tokens: &'a [Token],
+) -> Result<(Vec<Expression>, &'a [Token]), Error> {
+    let close = match open.kind {
+        TokenKind::Open('[') => ']',
+        TokenKind::Open('{') => '}',
+        _ => ')',
+    };
+    let mut res: Vec<Expression> = vec![];
+    let mut xs = tokens;
+    loop {
+        let (next_token, rest) = xs.split_first().ok_or(Error::incomplete(
+            format!("could not find closing `{}`", close),
+            Some(open.span),
+        ))?;
+        match next_token.kind {
+            // Skip the closing delimiter, head to the token after.
+            TokenKind::Close(c) if c == close => return Ok((res, rest)),
+            TokenKind::Close(c) => {
+                return Err(Error::parse(
+                    format!("expected `{}`, found `{}`", close, c),
+                    Some(next_token.span),
+                ))
+            }
+            _ => {}
+        }
+        let (exp, new_xs) = parse(xs)?;
+        res.push(exp);
+        xs = new_xs;
+    }
+}
+
+// The keys and values of a `{...}` literal are still unevaluated forms, so
+// `{:sum (+ 1 2)}` reads as a literal from `:sum` to the list `(+ 1 2)`.
+fn read_map(open: &Token, items: Vec<Expression>) -> Result<Expression, Error> {
+    if !items.len().is_multiple_of(2) {
+        return Err(Error::parse(
+            "expected a map literal to have a value for every key",
+            Some(open.span),
+        ));
+    }
+    Ok(Expression::MapLiteral(Rc::new(items)))
+}
+
+fn parse_atom(token: &str) -> Expression {
+    match token {
+        "true" => Expression::Bool(true),
+        "false" => Expression::Bool(false),
+        "nil" => Expression::Nil,
+        _ if token.len() > 1 && token.starts_with(':') => {
+            Expression::Keyword(token[1..].to_string())
+        }
+        _ => match Number::parse(token) {
+            Some(n) => Expression::Number(n),
+            None => Expression::Symbol(token.to_string()),
+        },
+    }
+}
+
+// Derived forms written in the lisp itself, loaded into every default_env.
+const PRELUDE: &str = include_str!("prelude.lisp");
+
+static GENSYM_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// A fresh symbol name that no other call returns.
+fn gensym(prefix: &str) -> String {
+    let n = GENSYM_COUNTER.fetch_add(1, atomic::Ordering::Relaxed);
+    format!("{}__{}", prefix, n)
+}
+
+fn default_env() -> Rc<RefCell<Environment>> {
+    let env = Rc::new(RefCell::new(Environment {
+        data: builtins::builtins(),
+        outer: None,
+    }));
+    if let Err(e) = parse_eval(PRELUDE, &env) {
+        panic!("{}", render_error(&e, "prelude.lisp", PRELUDE));
+    }
+
+    env
+}
+
+// Only `false` and `nil` are falsey; every other value, including `0`, `""`
+// and the empty list, counts as true in a test.
+fn is_truthy(exp: &Expression) -> bool {
+    !matches!(exp, Expression::Nil | Expression::Bool(false))
+}
+
+fn eval_if_args(arg_forms: &[Expression], env: &Rc<RefCell<Environment>>) -> Result<Step, Error> {
+    let (test_form, then_form, else_form) = match arg_forms {
+        [test_form, then_form] => (test_form, then_form, None),
+        [test_form, then_form, else_form] => (test_form, then_form, Some(else_form)),
+        _ => {
+            return Err(Error::arity(
+                "if expects a test form, a then form and an optional else form",
+            ))
+        }
+    };
+    if is_truthy(&eval(test_form, env)?) {
+        Ok(Step::Tail(then_form.clone(), env.clone()))
+    } else {
+        match else_form {
+            Some(else_form) => Ok(Step::Tail(else_form.clone(), env.clone())),
+            None => Ok(Step::Done(Expression::Nil)),
+        }
+    }
+}
+
+// Evaluates a sequence of forms for their effects, leaving the last one in
+// tail position.
+fn eval_body(forms: &[Expression], env: &Rc<RefCell<Environment>>) -> Result<Step, Error> {
+    match forms.split_last() {
+        Some((last, init)) => {
+            for form in init {
+                eval(form, env)?;
+            }
+            Ok(Step::Tail(last.clone(), env.clone()))
+        }
+        None => Ok(Step::Done(Expression::Nil)),
+    }
+}
+
+// Runs a `Step` to completion.
+fn eval_step(step: Step) -> Result<Expression, Error> {
+    match step {
+        Step::Done(res) => Ok(res),
+        Step::Tail(exp, env) => eval(&exp, &env),
+    }
+}
+
+// `and` stops at the first falsey value and `or` at the first truthy one,
+// returning it; the last form is left in tail position.
+fn eval_and_or_args(
+    name: &str,
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Step, Error> {
+    let short_circuit_on = name == "or";
+    match arg_forms.split_last() {
+        Some((last, init)) => {
+            for test_form in init {
+                let res = eval(test_form, env)?;
+                if is_truthy(&res) == short_circuit_on {
+                    return Ok(Step::Done(res));
+                }
+            }
+            Ok(Step::Tail(last.clone(), env.clone()))
+        }
+        None => Ok(Step::Done(Expression::Bool(!short_circuit_on))),
+    }
+}
+
+// `(cond (test body ...) ... (else body ...))` runs the body of the first
+// clause whose test is truthy; a clause without a body yields its test's
+// value, and `nil` is the result when no clause matches.
+fn eval_cond_args(arg_forms: &[Expression], env: &Rc<RefCell<Environment>>) -> Result<Step, Error> {
+    for clause in arg_forms {
+        let (test_form, body) = match clause {
+            Expression::List(list) if !list.is_empty() => (&list[0], &list[1..]),
+            _ => {
+                return Err(Error::type_error(format!(
+                    "expected a cond clause to be (test body ...), got {}",
+                    clause
+                )))
+            }
+        };
+        let test = match test_form {
+            Expression::Symbol(s) if s == "else" => Expression::Bool(true),
+            _ => eval(test_form, env)?,
+        };
+        if is_truthy(&test) {
+            if body.is_empty() {
+                return Ok(Step::Done(test));
+            }
+            return eval_body(body, env);
+        }
+    }
+    Ok(Step::Done(Expression::Nil))
+}
+
+// `(try body ... (catch e handler ...) (finally cleanup ...))` evaluates the
+// body; if it fails, the handler runs with `e` bound to the thrown value, or
+// to an error value for errors raised by the interpreter itself. The
+// cleanup forms run last either way and their values are discarded.
+fn eval_try_args(arg_forms: &[Expression], env: &Rc<RefCell<Environment>>) -> Result<Step, Error> {
+    let mut body = arg_forms;
+    let mut finally: Option<&[Expression]> = None;
+    if let Some((Expression::List(clause), init)) = body.split_last() {
+        if is_clause(clause, "finally") {
+            finally = Some(&clause[1..]);
+            body = init;
+        }
+    }
+    let mut catch: Option<(&str, &[Expression])> = None;
+    if let Some((Expression::List(clause), init)) = body.split_last() {
+        if is_clause(clause, "catch") {
+            match clause.get(1) {
+                Some(Expression::Symbol(var)) => catch = Some((var, &clause[2..])),
+                _ => return Err(Error::type_error("expected (catch symbol handler ...)")),
+            }
+            body = init;
+        }
+    }
+    if catch.is_none() && finally.is_none() {
+        return Err(Error::arity("try expects a catch or a finally clause"));
+    }
+
+    let res = eval_body(body, env).and_then(eval_step);
+    let res = match (res, catch) {
+        (Err(e), Some((var, handler))) => {
+            let caught = match e.kind {
+                ErrorKind::Thrown(exp) => exp,
+                _ => Expression::Error(Rc::new(e)),
+            };
+            let handler_env = Rc::new(RefCell::new(Environment {
+                data: HashMap::from([(var.to_string(), caught)]),
+                outer: Some(env.clone()),
+            }));
+            match finally {
+                Some(_) => eval_body(handler, &handler_env).and_then(eval_step),
+                None => return eval_body(handler, &handler_env),
+            }
+        }
+        (res, _) => res,
+    };
+    if let Some(cleanup) = finally {
+        eval_body(cleanup, env).and_then(eval_step)?;
+    }
+
+    res.map(Step::Done)
+}
+
+// Whether `list` is a clause such as `(catch e ...)` introduced by `name`.
+fn is_clause(list: &[Expression], name: &str) -> bool {
+    matches!(list.first(), Some(Expression::Symbol(s)) if s == name)
+}
+
+fn eval_def_args(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Expression, Error> {
+    let first_form = arg_forms
+        .first()
+        .ok_or(Error::arity("def expects a first form"))?;
+    let first_str = match first_form {
+        Expression::Symbol(s) => Ok(s.clone()),
+        _ => Err(Error::type_error("expected first form to be a symbol")),
+    }?;
+    let second_form = arg_forms
+        .get(1)
+        .ok_or(Error::arity("def expects a second form"))?;
+    if arg_forms.len() > 2 {
+        return Err(Error::arity("def can only have two forms"));
+    }
+    let second_eval = match eval(second_form, env)? {
+        Expression::Lambda(lambda) => Expression::Lambda(lambda.named(&first_str)),
+        exp => exp,
+    };
+    env_insert(first_str, second_eval, env);
+
+    Ok(Expression::Nil)
+}
+
+// Unlike `def`, `set!` never creates a binding: it updates the innermost
+// existing one, which may belong to an enclosing scope.
+fn eval_set_args(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Expression, Error> {
+    let (name_form, value_form) = match arg_forms {
+        [name_form, value_form] => (name_form, value_form),
+        _ => return Err(Error::arity("set! expects a symbol and a value form")),
+    };
+    let value = match name_form {
+        Expression::Symbol(_) | Expression::Alias(_) => eval(value_form, env)?,
+        _ => return Err(Error::type_error("expected first form to be a symbol")),
+    };
+    match name_form {
+        Expression::Alias(alias) => env_set(&alias.name, value, &alias.env()?)?,
+        Expression::Symbol(name) => env_set(name, value, env)?,
+        _ => unreachable!("checked above"),
+    }
+
+    Ok(Expression::Nil)
+}
+
+fn eval_lambda_args(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Expression, Error> {
+    let params_exp = arg_forms
+        .first()
+        .ok_or(Error::arity("fn expects an args form"))?;
+    let body = &arg_forms[1..];
+    if body.is_empty() {
+        return Err(Error::arity("fn expects a body form"));
+    }
+
+    Ok(Expression::Lambda(Lambda {
+        name: None,
+        body: Rc::new(body.to_vec()),
+        params: Rc::new(parse_params(params_exp)?),
+        env: Captured::Strong(env.clone()),
+    }))
+}
+
+// `let`, `let*` and `letrec` all evaluate their body in one new frame. `let`
+// evaluates the values in the enclosing environment, while `let*` and
+// `letrec` evaluate them in the new frame as it fills up, so later bindings
+// (and lambdas, recursively) can refer to earlier ones.
+fn eval_let_args(
+    name: &str,
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Step, Error> {
+    if let (true, Some(Expression::Symbol(loop_name))) = (name == "let", arg_forms.first()) {
+        return eval_named_let_args(loop_name, &arg_forms[1..], env);
+    }
+    let (bindings_form, body) = match arg_forms {
+        [bindings_form, body @ ..] if !body.is_empty() => (bindings_form, body),
+        _ => {
+            return Err(Error::arity(format!(
+                "{} expects a binding list and a body",
+                name
+            )))
+        }
+    };
+    let bindings = parse_let_bindings(bindings_form)?;
+    // `let*` binds one name at a time, so a later binding may shadow an
+    // earlier one.
+    if name != "let*" {
+        ensure_distinct(bindings.iter().map(|(k, _)| k), name)?;
+    }
+    let new_env = Rc::new(RefCell::new(Environment {
+        data: HashMap::new(),
+        outer: Some(env.clone()),
+    }));
+    for (k, v_form) in bindings {
+        let v = if name == "let" {
+            eval(v_form, env)?
+        } else {
+            eval(v_form, &new_env)?
+        };
+        env_insert(k, v, &new_env);
+    }
+
+    eval_body(body, &new_env)
+}
+
+// `(let loop ((i 0)) body)` binds `loop` to a lambda over the bound names
+// whose body is `body`, and calls it with the initial values.
+fn eval_named_let_args(
+    loop_name: &str,
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Step, Error> {
+    let (bindings_form, body) = match arg_forms {
+        [bindings_form, body @ ..] if !body.is_empty() => (bindings_form, body),
+        _ => {
+            return Err(Error::arity(
+                "named let expects a name, a binding list and a body",
+            ))
+        }
+    };
+    let (names, init_forms): (Vec<String>, Vec<Expression>) = parse_let_bindings(bindings_form)?
+        .into_iter()
+        .map(|(k, v_form)| (k, v_form.clone()))
+        .unzip();
+    ensure_distinct(&names, "let")?;
+    let loop_env = Rc::new(RefCell::new(Environment {
+        data: HashMap::new(),
+        outer: Some(env.clone()),
+    }));
+    let lambda = Lambda {
+        name: Some(Rc::from(loop_name)),
+        params: Rc::new(Params {
+            required: names,
+            ..Params::default()
+        }),
+        body: Rc::new(body.to_vec()),
+        env: Captured::Strong(loop_env.clone()),
+    };
+    env_insert(
+        loop_name.to_string(),
+        Expression::Lambda(lambda.clone()),
+        &loop_env,
+    );
+    let new_env = env_for_lambda(&lambda, &eval_forms(&init_forms, env)?)?;
+
+    eval_body(body, &new_env)
+}
+
+fn parse_let_bindings(form: &Expression) -> Result<Vec<(String, &Expression)>, Error> {
+    let list = match form {
+        Expression::List(list) => list,
+        _ => return Err(Error::type_error("expected a binding list")),
+    };
+    list.iter()
+        .map(|binding| match binding {
+            Expression::List(pair) => match pair.as_slice() {
+                [Expression::Symbol(k), v_form] => Ok((k.clone(), v_form)),
+                _ => Err(Error::type_error(format!(
+                    "expected a binding to be (name value), got {}",
+                    binding
+                ))),
+            },
+            _ => Err(Error::type_error(format!(
+                "expected a binding to be (name value), got {}",
+                binding
+            ))),
+        })
+        .collect()
+}
+
+// Fails on the first name that appears more than once among `names`, which
+// `place` (a binding form or an argument list) binds at the same time.
+fn ensure_distinct<'a>(
+    names: impl IntoIterator<Item = &'a String>,
+    place: &str,
+) -> Result<(), Error> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            return Err(Error::parse(
+                format!("{} is bound more than once in {}", name, place),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn eval_quote_args(arg_forms: &[Expression]) -> Result<Expression, Error> {
+    match arg_forms {
+        [exp] => quoted(exp),
+        _ => Err(Error::arity(format!(
+            "quote expects 1 form, got {}",
+            arg_forms.len()
+        ))),
+    }
+}
+
+// The value a quoted form stands for. That is the form itself, except that
+// the `{...}` literals in it become maps.
+fn quoted(exp: &Expression) -> Result<Expression, Error> {
+    Ok(quoted_if_changed(exp)?.unwrap_or_else(|| exp.clone()))
+}
+
+// Like `quoted`, but `None` when there are no map literals in `exp`, which
+// then stands for itself.
+fn quoted_if_changed(exp: &Expression) -> Result<Option<Expression>, Error> {
+    let _guard = StackGuard::enter()?;
+    match exp {
+        Expression::List(items) => {
+            Ok(quoted_items(items)?.map(|items| Expression::List(items.into())))
+        }
+        Expression::Vector(items) => {
+            Ok(quoted_items(items)?.map(|items| Expression::Vector(Rc::new(items))))
+        }
+        Expression::MapLiteral(items) => {
+            let items = items
+                .iter()
+                .map(quoted)
+                .collect::<Result<Vec<_>, Error>>()?;
+            map_from_literal(items).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn quoted_items(items: &[Expression]) -> Result<Option<Vec<Expression>>, Error> {
+    let mut res: Option<Vec<Expression>> = None;
+    for (i, item) in items.iter().enumerate() {
+        match (quoted_if_changed(item)?, &mut res) {
+            (Some(item), res) => res.get_or_insert_with(|| items[..i].to_vec()).push(item),
+            (None, Some(res)) => res.push(item.clone()),
+            (None, None) => {}
+        }
+    }
+    Ok(res)
+}
+
+fn eval_quasiquote_args(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Expression, Error> {
+    match arg_forms {
+        [exp] => quasiquote(exp, env, 1),
+        _ => Err(Error::arity(format!(
+            "quasiquote expects 1 form, got {}",
+            arg_forms.len()
+        ))),
+    }
+}
+
+// Fills in the template `exp`. `depth` counts the quasiquotes around it, and
+// only unquotes that bring it back down to zero are evaluated. Vector and map
+// templates are filled in like lists.
+fn quasiquote(
+    exp: &Expression,
+    env: &Rc<RefCell<Environment>>,
+    depth: usize,
+) -> Result<Expression, Error> {
+    let list = match exp {
+        Expression::List(list) => list,
+        Expression::Vector(items) => {
+            return Ok(Expression::Vector(Rc::new(quasiquote_items(
+                items, env, depth,
+            )?)))
+        }
+        Expression::MapLiteral(items) => {
+            return map_from_literal(quasiquote_items(items, env, depth)?)
+        }
+        _ => return Ok(exp.clone()),
+    };
+    if let [Expression::Symbol(s), x] = list.as_slice() {
+        match s.as_ref() {
+            "unquote" if depth == 1 => return eval(x, env),
+            "unquote-splicing" if depth == 1 => {
+                return Err(Error::parse("unquote-splicing outside of a list", None))
+            }
+            "unquote" | "unquote-splicing" => {
+                return Ok(Expression::List(
+                    vec![list[0].clone(), quasiquote(x, env, depth - 1)?].into(),
+                ))
+            }
+            "quasiquote" => {
+                return Ok(Expression::List(
+                    vec![list[0].clone(), quasiquote(x, env, depth + 1)?].into(),
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(Expression::List(quasiquote_items(list, env, depth)?.into()))
+}
+
+// Fills in the items of a list or vector template, splicing in the items of
+// the lists that its `,@` forms evaluate to.
+fn quasiquote_items(
+    items: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+    depth: usize,
+) -> Result<Vec<Expression>, Error> {
+    let mut res: Vec<Expression> = vec![];
+    for item in items {
+        match item {
+            Expression::List(inner) if depth == 1 && is_form(inner, "unquote-splicing") => {
+                res.extend(parse_single_list(&eval(&inner[1], env)?)?);
+            }
+            _ => res.push(quasiquote(item, env, depth)?),
+        }
+    }
+    Ok(res)
+}
+
+// Whether `list` is `(name x)`.
+fn is_form(list: &[Expression], name: &str) -> bool {
+    matches!(list, [Expression::Symbol(s), _] if s == name)
+}
+
+fn eval_defmacro_args(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Expression, Error> {
+    let (name_form, lambda_forms) = arg_forms
+        .split_first()
+        .ok_or(Error::arity("defmacro expects a name"))?;
+    let name = match name_form {
+        Expression::Symbol(s) => s.clone(),
+        _ => return Err(Error::type_error("expected macro name to be a symbol")),
+    };
+    let lambda = match eval_lambda_args(lambda_forms, env)? {
+        Expression::Lambda(lambda) => lambda,
+        _ => unreachable!("eval_lambda_args always builds a lambda"),
+    };
+    env.borrow_mut()
+        .data
+        .insert(name.clone(), Expression::Macro(lambda.named(&name)));
+
+    Ok(Expression::Nil)
+}
+
+fn eval_define_syntax_args(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Expression, Error> {
+    let (name_form, spec) = match arg_forms {
+        [name_form, spec] => (name_form, spec),
+        _ => {
+            return Err(Error::arity(format!(
+                "define-syntax expects 2 forms, got {}",
+                arg_forms.len()
+            )))
+        }
+    };
+    let name = match name_form {
+        Expression::Symbol(s) => s.clone(),
+        _ => return Err(Error::type_error("expected macro name to be a symbol")),
+    };
+    let rules = SyntaxRules::new(spec, env)?;
+    env.borrow_mut()
+        .data
+        .insert(name, Expression::Syntax(Rc::new(rules)));
+
+    Ok(Expression::Nil)
+}
+
+// Runs the macro on the unevaluated argument forms, producing the form to
+// evaluate in their place.
+fn expand_macro(m: &Expression, arg_forms: &[Expression]) -> Result<Expression, Error> {
+    match m {
+        Expression::Macro(lambda) => {
+            let new_env = env_for_lambda(lambda, arg_forms)?;
+            eval_step(eval_body(&lambda.body, &new_env)?)
+        }
+        Expression::Syntax(rules) => rules.expand(arg_forms),
+        other => Err(Error::type_error(format!("{} is not a macro", other))),
+    }
+}
+
+// The macro `form` is a call to, if any.
+fn macro_for(form: &Expression, env: &Rc<RefCell<Environment>>) -> Option<Expression> {
+    match form {
+        Expression::List(list) => match list.first() {
+            Some(Expression::Symbol(s)) => match env_get(s, env) {
+                Some(m @ Expression::Macro(_)) | Some(m @ Expression::Syntax(_)) => Some(m),
+                _ => None,
+            },
+            Some(Expression::Alias(alias)) => match env_get(&alias.name, &alias.env().ok()?) {
+                Some(m @ Expression::Macro(_)) | Some(m @ Expression::Syntax(_)) => Some(m),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn eval_macroexpand_args(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+    repeat: bool,
+) -> Result<Expression, Error> {
+    let mut form = match arg_forms {
+        [form] => eval(form, env)?,
+        _ => {
+            return Err(Error::arity(format!(
+                "macroexpand expects 1 form, got {}",
+                arg_forms.len()
+            )))
+        }
+    };
+    while let Some(m) = macro_for(&form, env) {
+        let arg_forms = match &form {
+            Expression::List(list) => list[1..].to_vec(),
+            _ => unreachable!("macro_for only matches lists"),
+        };
+        form = expand_macro(&m, &arg_forms)?;
+        if !repeat {
+            break;
+        }
+    }
+    Ok(form)
+}
+
+// Evaluates the arguments of a special form, given its name.
+type SpecialForm = fn(&str, &[Expression], &Rc<RefCell<Environment>>) -> Result<Step, Error>;
+
+// The special form `exp` names, if any. Each is called through a pointer so
+// that none of their temporaries take up room in the frame of `eval_list`.
+fn built_in_form(exp: &Expression) -> Option<SpecialForm> {
+    let name = match exp {
+        Expression::Symbol(s) => s.as_str(),
+        _ => return None,
+    };
+    let form: SpecialForm = match name {
+        "if" => |_, arg_forms, env| eval_if_args(arg_forms, env),
+        "def" => |_, arg_forms, env| eval_def_args(arg_forms, env).map(Step::Done),
+        "set!" => |_, arg_forms, env| eval_set_args(arg_forms, env).map(Step::Done),
+        "fn" => |_, arg_forms, env| eval_lambda_args(arg_forms, env).map(Step::Done),
+        "quote" => |_, arg_forms, _| eval_quote_args(arg_forms).map(Step::Done),
+        "quasiquote" => |_, arg_forms, env| eval_quasiquote_args(arg_forms, env).map(Step::Done),
+        "unquote" | "unquote-splicing" => |name, _, _| {
+            Err(Error::parse(
+                format!("{} outside of quasiquote", name),
+                None,
+            ))
+        },
+        "defmacro" => |_, arg_forms, env| eval_defmacro_args(arg_forms, env).map(Step::Done),
+        "macroexpand-1" => {
+            |_, arg_forms, env| eval_macroexpand_args(arg_forms, env, false).map(Step::Done)
+        }
+        "macroexpand" => {
+            |_, arg_forms, env| eval_macroexpand_args(arg_forms, env, true).map(Step::Done)
+        }
+        "define-syntax" => {
+            |_, arg_forms, env| eval_define_syntax_args(arg_forms, env).map(Step::Done)
+        }
+        "let" | "let*" | "letrec" => eval_let_args,
+        "begin" | "do" => |_, arg_forms, env| eval_body(arg_forms, env),
+        "and" | "or" => eval_and_or_args,
+        "cond" => |_, arg_forms, env| eval_cond_args(arg_forms, env),
+        "try" => |_, arg_forms, env| eval_try_args(arg_forms, env),
+        _ => return None,
+    };
+    Some(form)
+}
+
+fn env_get(k: &str, env: &Rc<RefCell<Environment>>) -> Option<Expression> {
+    let env = env.borrow();
+    match env.data.get(k) {
+        Some(Expression::Lambda(lambda)) => Some(Expression::Lambda(Lambda {
+            env: lambda.env().map_or(lambda.env.clone(), Captured::Strong),
+            ..lambda.clone()
+        })),
+        Some(exp) => Some(exp.clone()),
+        None => match &env.outer {
+            Some(outer_env) => env_get(k, outer_env),
+            None => None,
+        },
+    }
+}
+
+fn env_set(k: &str, v: Expression, env: &Rc<RefCell<Environment>>) -> Result<(), Error> {
+    let mut frame = env.borrow_mut();
+    match frame.data.get_mut(k) {
+        Some(exp) => {
+            *exp = captured_weakly(v, env);
+            Ok(())
+        }
+        None => match &frame.outer {
+            Some(outer_env) => env_set(k, v, outer_env),
+            None => Err(Error::unbound_symbol(k)),
+        },
+    }
+}
+
+// Binds `k` in the frame `env` itself.
+fn env_insert(k: String, v: Expression, env: &Rc<RefCell<Environment>>) {
+    let v = captured_weakly(v, env);
+    env.borrow_mut().data.insert(k, v);
+}
+
+// `v` as it is stored in `env`: a lambda that closes over `env` only holds it
+// weakly there, see `Captured`.
+fn captured_weakly(v: Expression, env: &Rc<RefCell<Environment>>) -> Expression {
+    match v {
+        Expression::Lambda(lambda) if lambda.env_ptr() == Rc::as_ptr(env) => {
+            Expression::Lambda(Lambda {
+                env: Captured::Weak(Rc::downgrade(env)),
+                ..lambda
+            })
+        }
+        v => v,
+    }
+}
+
+// Reads a parameter list such as `(a b (c 1) & rest)` or
+// `(a &key (verbose false) width)`:
+//
+//   - plain symbols are required parameters,
+//   - `(name default)` is an optional parameter, which gets the value of the
+//     default form when the argument is left out,
+//   - `& name` (or `. name`) collects the remaining arguments in a list,
+//   - parameters after `&key` are passed by name, as in `(f 1 :verbose true)`;
+//     ones without a default are `nil` when left out.
+//
+// Optional and keyword parameters can't be mixed, and neither can a rest
+// parameter and keyword parameters, since the arguments they take would be
+// ambiguous.
+fn parse_params(form: &Expression) -> Result<Params, Error> {
+    let list = match form {
+        Expression::List(list) => list,
+        _ => return Err(Error::type_error("expected args form to be a list")),
+    };
+    let mut params = Params::default();
+    let mut keys = false;
+    let mut iter = list.iter();
+    while let Some(param) = iter.next() {
+        match param {
+            Expression::Symbol(s) if s == "&" || s == "." => {
+                params.rest = match (iter.next(), iter.next()) {
+                    (Some(Expression::Symbol(rest)), None) => Some(rest.clone()),
+                    _ => {
+                        return Err(Error::type_error(format!(
+                            "expected a single symbol after {} in the argument list",
+                            s
+                        )))
+                    }
+                };
+            }
+            Expression::Symbol(s) if s == "&key" => keys = true,
+            Expression::Symbol(s) if keys => params.keys.push((s.clone(), Expression::Nil)),
+            Expression::Symbol(s) => {
+                if !params.optional.is_empty() {
+                    return Err(Error::type_error(format!(
+                        "required parameter {} follows an optional one",
+                        s
+                    )));
+                }
+                params.required.push(s.clone());
+            }
+            Expression::List(pair) => match pair.as_slice() {
+                [Expression::Symbol(name), default] if keys => {
+                    params.keys.push((name.clone(), default.clone()))
+                }
+                [Expression::Symbol(name), default] => {
+                    params.optional.push((name.clone(), default.clone()))
+                }
+                _ => {
+                    return Err(Error::type_error(format!(
+                        "expected an optional parameter to be (name default), got {}",
+                        param
+                    )))
+                }
+            },
+            _ => return Err(Error::type_error("expected symbols in the argument list")),
+        }
+    }
+    if !params.keys.is_empty() && (!params.optional.is_empty() || params.rest.is_some()) {
+        return Err(Error::type_error(
+            "keyword parameters can't be combined with optional or rest parameters",
+        ));
+    }
+    let names = params
+        .required
+        .iter()
+        .chain(params.optional.iter().map(|(k, _)| k));
+    let names = names
+        .chain(&params.rest)
+        .chain(params.keys.iter().map(|(k, _)| k));
+    ensure_distinct(names, "an argument list")?;
+
+    Ok(params)
+}
+
+// Binds the arguments of a call to `lambda` in a new frame. Default forms are
+// evaluated in that frame, so they can refer to the parameters before them.
+fn env_for_lambda(lambda: &Lambda, vs: &[Expression]) -> Result<Rc<RefCell<Environment>>, Error> {
+    let params = &lambda.params;
+    let min = params.required.len();
+    let max = min + params.optional.len();
+    let variadic = params.rest.is_some() || !params.keys.is_empty();
+    if vs.len() < min || (!variadic && vs.len() > max) {
+        let expected = match (variadic, min == max) {
+            (true, _) => format!("at least {}", min),
+            (false, true) => min.to_string(),
+            (false, false) => format!("{} to {}", min, max),
+        };
+        return Err(Error::arity(format!(
+            "expected {} arguments, got {}",
+            expected,
+            vs.len()
+        )));
+    }
+    let new_env = Rc::new(RefCell::new(Environment {
+        data: HashMap::new(),
+        outer: Some(lambda.env()?),
+    }));
+    for (k, v) in params.required.iter().zip(vs) {
+        new_env.borrow_mut().data.insert(k.clone(), v.clone());
+    }
+    for (i, (k, default)) in params.optional.iter().enumerate() {
+        let v = match vs.get(min + i) {
+            Some(v) => v.clone(),
+            None => eval(default, &new_env)?,
+        };
+        env_insert(k.clone(), v, &new_env);
+    }
+    let extra = vs.get(max..).unwrap_or(&[]);
+    if let Some(rest) = &params.rest {
+        new_env
+            .borrow_mut()
+            .data
+            .insert(rest.clone(), Expression::List(extra.to_vec().into()));
+    }
+    if !params.keys.is_empty() {
+        let mut passed: HashMap<&str, &Expression> = HashMap::new();
+        for pair in extra.chunks(2) {
+            let key = match &pair[0] {
+                Expression::Keyword(key) if params.keys.iter().any(|(k, _)| k == key) => key,
+                other => {
+                    return Err(Error::type_error(format!(
+                        "unexpected keyword argument {}",
+                        other
+                    )))
+                }
+            };
+            let v = pair
+                .get(1)
+                .ok_or(Error::arity(format!("missing a value for :{}", key)))?;
+            passed.insert(key, v);
+        }
+        for (k, default) in &params.keys {
+            let v = match passed.get(k.as_str()) {
+                Some(v) => (*v).clone(),
+                None => eval(default, &new_env)?,
+            };
+            env_insert(k.clone(), v, &new_env);
+        }
+    }
+
+    Ok(new_env)
+}
+
+fn eval_forms(
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Vec<Expression>, Error> {
+    let mut res = Vec::with_capacity(arg_forms.len());
+    for x in arg_forms {
+        res.push(eval(x, env)?);
+    }
+    Ok(res)
+}
+
+fn eval_call(
+    first_form: &Expression,
+    arg_forms: &[Expression],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Step, Error> {
+    let first_eval = eval(first_form, env)?;
+    match first_eval {
+        Expression::Function(f) => Ok(Step::Done(f.call(&eval_forms(arg_forms, env)?)?)),
+        Expression::Lambda(lambda) => {
+            let new_env = env_for_lambda(&lambda, &eval_forms(arg_forms, env)?)?;
+            eval_body(&lambda.body, &new_env)
+        }
+        m @ Expression::Macro(_) | m @ Expression::Syntax(_) => {
+            Ok(Step::Tail(expand_macro(&m, arg_forms)?, env.clone()))
+        }
+        other => Err(Error::type_error(format!("{} is not a function", other))),
+    }
+}
+
+// Calls a function value with already evaluated arguments, so builtins such
+// as `map` can call back into user code.
+fn apply(f: &Expression, args: &[Expression]) -> Result<Expression, Error> {
+    match f {
+        Expression::Function(f) => f.call(args),
+        Expression::Lambda(lambda) => {
+            let new_env = env_for_lambda(lambda, args)?;
+            eval_step(eval_body(&lambda.body, &new_env)?)
+        }
+        other => Err(Error::type_error(format!("{} is not a function", other))),
+    }
+}
+
+// Lists and vector and map literals are evaluated out of line to keep
+// `eval`'s own stack frame small: it is on the stack once for every level of
+// nesting in the program.
+#[inline(never)]
+fn eval_list(list: &List, env: &Rc<RefCell<Environment>>) -> Result<Step, Error> {
+    let (first_form, arg_forms) = match list.split_first() {
+        Some(split) => split,
+        None => return Err(Error::type_error("expected a non-empty list")),
+    };
+    let step = match (built_in_form(first_form), first_form) {
+        (Some(form), Expression::Symbol(name)) => form(name, arg_forms, env),
+        _ => eval_call(first_form, arg_forms, env),
+    };
+    step.map_err(|e| e.or_span(list.span()))
+}
+
+#[inline(never)]
+fn eval_vector(items: &[Expression], env: &Rc<RefCell<Environment>>) -> Result<Expression, Error> {
+    Ok(Expression::Vector(Rc::new(eval_forms(items, env)?)))
+}
+
+#[inline(never)]
+fn eval_map(items: &[Expression], env: &Rc<RefCell<Environment>>) -> Result<Expression, Error> {
+    map_from_literal(eval_forms(items, env)?)
+}
+
+// The map of a `{...}` literal, given its evaluated or quoted keys and
+// values, alternating. The keys must all differ.
+fn map_from_literal(items: Vec<Expression>) -> Result<Expression, Error> {
+    if !items.len().is_multiple_of(2) {
+        return Err(Error::type_error(
+            "expected a map literal to have a value for every key",
+        ));
+    }
+    let mut map = Map::new();
+    let mut iter = items.into_iter();
+    while let (Some(k), Some(v)) = (iter.next(), iter.next()) {
+        if map.contains_key(&k) {
+            return Err(Error::type_error(format!(
+                "duplicate key {} in a map literal",
+                k
+            )));
+        }
+        map.insert(k, v)?;
+    }
+    Ok(Expression::Map(Rc::new(map)))
+}
+
+// Half the stack Rust gives the threads it spawns.
+const DEFAULT_STACK_LIMIT: usize = 1024 * 1024;
+
+thread_local! {
+    // Where on the stack the outermost `eval` running on this thread started.
+    static STACK_START: Cell<Option<usize>> = const { Cell::new(None) };
+    static STACK_LIMIT: Cell<usize> = const { Cell::new(DEFAULT_STACK_LIMIT) };
+}
+
+/// Sets how many bytes of stack reading and evaluation on the current thread
+/// may take up before they fail with a `stack-overflow` error, which programs
+/// can catch, instead of overflowing the stack and aborting the process. The
+/// default, 1 MiB, suits threads with the 2 MiB stack that
+/// `std::thread::spawn` gives them; raise it only for a thread with a larger
+/// stack.
+pub fn set_stack_limit(bytes: usize) {
+    STACK_LIMIT.with(|limit| limit.set(bytes));
+}
+
+// Held by every running `eval`; checks on entry that the stack has room left.
+struct StackGuard {
+    outermost: bool,
+}
+
+impl StackGuard {
+    fn enter() -> Result<StackGuard, Error> {
+        let marker = 0u8;
+        let here = &marker as *const u8 as usize;
+        STACK_START.with(|start| match start.get() {
+            None => {
+                start.set(Some(here));
+                Ok(StackGuard { outermost: true })
+            }
+            Some(start) if start.abs_diff(here) > STACK_LIMIT.with(Cell::get) => {
+                Err(Error::stack_overflow())
+            }
+            Some(_) => Ok(StackGuard { outermost: false }),
+        })
+    }
+}
+
+impl Drop for StackGuard {
+    fn drop(&mut self) {
+        if self.outermost {
+            STACK_START.with(|start| start.set(None));
+        }
+    }
+}
+
+fn eval(exp: &Expression, env: &Rc<RefCell<Environment>>) -> Result<Expression, Error> {
+    let _guard = StackGuard::enter()?;
+    let mut exp = exp.clone();
+    let mut env = env.clone();
+    loop {
+        let step = match &exp {
+            Expression::Symbol(k) => {
+                return env_get(k, &env).ok_or_else(|| Error::unbound_symbol(k))
+            }
+            Expression::Alias(alias) => {
+                return env_get(&alias.name, &alias.env()?)
+                    .ok_or_else(|| Error::unbound_symbol(&alias.name))
+            }
+            Expression::Nil => return Ok(exp.clone()),
+            Expression::Bool(_a) => return Ok(exp.clone()),
+            Expression::Vector(items) => return eval_vector(items, &env),
+            Expression::MapLiteral(items) => return eval_map(items, &env),
+            // `()` evaluates to the empty list.
+            Expression::List(list) if list.is_empty() => return Ok(exp.clone()),
+            Expression::Number(_a) => return Ok(exp.clone()),
+            Expression::Str(_a) => return Ok(exp.clone()),
+            Expression::Keyword(_a) => return Ok(exp.clone()),
+            Expression::Map(_)
+            | Expression::Box(_)
+            | Expression::Array(_)
+            | Expression::Error(_) => return Ok(exp.clone()),
+
+            Expression::List(list) => eval_list(list, &env)?,
+            Expression::Function(_)
+            | Expression::Lambda(_)
+            | Expression::Macro(_)
+            | Expression::Syntax(_) => {
+                return Err(Error::type_error(format!("unexpected form {}", exp)))
+            }
+        };
+        match step {
+            Step::Done(res) => return Ok(res),
+            Step::Tail(next_exp, next_env) => {
+                exp = next_exp;
+                env = next_env;
+            }
+        }
+    }
+}
+
+// Evaluates every top-level form in `expr` in order and returns the value of
+// the last one, or `None` when there is nothing but whitespace and comments.
+fn parse_eval(expr: &str, env: &Rc<RefCell<Environment>>) -> Result<Option<Expression>, Error> {
+    let tokens = tokenize(expr)?;
+    let mut xs: &[Token] = &tokens;
+    let mut evaled_exp = None;
+    while !xs.is_empty() {
+        let (parsed_exp, rest) = parse(xs)?;
+        let form_tokens = &xs[..xs.len() - rest.len()];
+        evaled_exp =
+            Some(eval(&parsed_exp, env).map_err(|e| locate_error(e, &tokens, form_tokens))?);
+        xs = rest;
+    }
+
+    Ok(evaled_exp)
+}
+
+// Errors raised inside a list form carry its span already; this settles the
+// location of one that escaped the top-level form in `form_tokens`, taken
+// from `tokens`, all of the source. An error without a span, or with one from
+// another source, points at the whole top-level form. An unbound symbol is
+// narrowed down to its first occurrence in the form that failed.
+fn locate_error(mut e: Error, tokens: &[Token], form_tokens: &[Token]) -> Error {
+    let (first, last) = match (form_tokens.first(), form_tokens.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return e,
+    };
+    let form_span = Span {
+        end: last.span.end,
+        ..first.span
+    };
+    if e.span.is_some_and(|span| span.source != form_span.source) {
+        e.span = None;
+    }
+    if let ErrorKind::UnboundSymbol(name) = &e.kind {
+        let within = e.span.unwrap_or(form_span);
+        let symbol_span = tokens
+            .iter()
+            .find(|token| {
+                token.kind == TokenKind::Atom(name.clone()) && within.contains(&token.span)
+            })
+            .map(|token| token.span);
+        if symbol_span.is_some() {
+            e.span = symbol_span;
+        }
+    }
+    e.or_span(Some(form_span))
+}
+
+/// Whether `expr` only fails to parse because it stops in the middle of a
+/// form, so a REPL should keep reading lines instead of reporting an error.
+pub fn is_incomplete(expr: &str) -> bool {
+    let parsed = tokenize(expr).and_then(|tokens| {
+        let mut xs: &[Token] = &tokens;
+        while !xs.is_empty() {
+            xs = parse(xs)?.1;
+        }
+        Ok(())
+    });
+    matches!(
+        parsed,
+        Err(Error {
+            kind: ErrorKind::Incomplete(_),
+            ..
+        })
+    )
+}
+
+/// An interpreter with its own global environment, which starts out with the
+/// builtins and the prelude. Definitions made by evaluated code persist
+/// across calls.
+pub struct Interpreter {
+    env: Rc<RefCell<Environment>>,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter { env: default_env() }
+    }
+
+    /// Evaluates every form in `source` and returns the value of the last
+    /// one, or `nil` if there are none.
+    pub fn eval_str(&self, source: &str) -> Result<Expression, Error> {
+        parse_eval(source, &self.env).map(|res| res.unwrap_or(Expression::Nil))
+    }
+
+    /// Like [`Interpreter::eval_str`], with the source read from a file.
+    pub fn eval_file(&self, path: impl AsRef<Path>) -> Result<Expression, Error> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| Error::io(&format!("cannot read {}", path.display()), &e))?;
+        self.eval_str(&source)
+    }
+
+    /// Binds `name` to `value` in the global environment, replacing any
+    /// previous binding.
+    pub fn define(&self, name: &str, value: Expression) {
+        self.env.borrow_mut().data.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Expression> {
+        env_get(name, &self.env)
+    }
+
+    /// Binds `name` to a Rust function or closure whose parameters and
+    /// result convert from and to lisp values, e.g.
+    /// `interpreter.define_fn("clamp", |x: f64, lo: f64, hi: f64| x.max(lo).min(hi))`.
+    /// Calls with the wrong number of arguments or arguments of the wrong
+    /// type fail with an arity or a type error.
+    pub fn define_fn<Args>(&self, name: &str, f: impl NativeFn<Args>) {
+        self.define(name, Expression::Function(f.into_function(name)));
+    }
+
+    /// Calls the function bound to `name` with already evaluated arguments.
+    pub fn call(&self, name: &str, args: &[Expression]) -> Result<Expression, Error> {
+        let f = self.get(name).ok_or_else(|| Error::unbound_symbol(name))?;
+        apply(&f, args)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
@@ -0,0 +1,101 @@
+use super::{Error, Expression};
+use std::collections::HashMap;
+
+/// An immutable hash map, written `{:a 1 :b 2}`.
+///
+/// Keys can be any data, including lists and other maps, but not procedures
+/// or mutable objects. Two keys are the same when they are `equal?`. Entries
+/// keep the order they were first added in.
+#[derive(Clone, Debug, Default)]
+pub struct Map {
+    entries: Vec<(Expression, Expression)>,
+    // The position of each entry, by the printed form of its key with `-0.0`
+    // written as `0.0`, which is the same for keys that are `equal?` and
+    // different otherwise.
+    index: HashMap<String, usize>,
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Expression) -> Option<&Expression> {
+        let key = map_key(key).ok()?;
+        self.index.get(&key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, key: &Expression) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Fails if `key` is not data.
+    pub fn insert(&mut self, key: Expression, value: Expression) -> Result<(), Error> {
+        let key_str = map_key(&key)?;
+        match self.index.get(&key_str) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(key_str, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &Expression) {
+        let i = match map_key(key).ok().and_then(|key| self.index.remove(&key)) {
+            Some(i) => i,
+            None => return,
+        };
+        self.entries.remove(i);
+        for position in self.index.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Expression, &Expression)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+// Maps with the same entries are equal whatever order they were added in.
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+fn map_key(exp: &Expression) -> Result<String, Error> {
+    if is_data(exp) {
+        Ok(exp.key_string())
+    } else {
+        Err(Error::type_error(format!("{} can't be a map key", exp)))
+    }
+}
+
+// Whether `exp` is a value that prints as a literal, so that its printed form
+// identifies it.
+fn is_data(exp: &Expression) -> bool {
+    match exp {
+        Expression::Nil
+        | Expression::Bool(_)
+        | Expression::Symbol(_)
+        | Expression::Keyword(_)
+        | Expression::Number(_)
+        | Expression::Str(_) => true,
+        Expression::List(items) => items.iter().all(is_data),
+        Expression::Vector(items) => items.iter().all(is_data),
+        Expression::Map(map) => map.iter().all(|(k, v)| is_data(k) && is_data(v)),
+        _ => false,
+    }
+}
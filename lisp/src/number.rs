@@ -0,0 +1,450 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// The numeric tower: exact integers that grow into big integers when they
+/// overflow, exact rationals, and inexact floats.
+///
+/// Values are kept normalised, so a `Big` never fits in an `i64` and a
+/// `Rational` never has a denominator of one.
+#[derive(Clone, Debug)]
+pub enum Number {
+    Int(i64),
+    Big(BigInt),
+    Rational(BigRational),
+    Float(f64),
+}
+
+impl Number {
+    /// Reads a numeric literal: `42`, `-7`, `123456789012345678901234`, `1/3`,
+    /// `0.5`, `1e10`. Anything else, including `inf` and `nan`, is not a number.
+    pub fn parse(token: &str) -> Option<Number> {
+        let unsigned = token.strip_prefix(['+', '-']).unwrap_or(token);
+        let unsigned = unsigned.strip_prefix('.').unwrap_or(unsigned);
+        if !unsigned.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        if let Ok(n) = token.parse::<i64>() {
+            return Some(Number::Int(n));
+        }
+        if let Ok(n) = token.parse::<BigInt>() {
+            return Some(Number::from_bigint(n));
+        }
+        if let Some((numer, denom)) = token.split_once('/') {
+            let numer = numer.parse::<BigInt>().ok()?;
+            let denom = denom.parse::<BigInt>().ok()?;
+            if denom.is_zero() || denom.is_negative() {
+                return None;
+            }
+            return Some(Number::from_rational(BigRational::new(numer, denom)));
+        }
+        match token.parse::<f64>() {
+            Ok(x) if x.is_finite() => Some(Number::Float(x)),
+            _ => None,
+        }
+    }
+
+    fn from_bigint(n: BigInt) -> Number {
+        match n.to_i64() {
+            Some(n) => Number::Int(n),
+            None => Number::Big(n),
+        }
+    }
+
+    fn from_rational(r: BigRational) -> Number {
+        if r.is_integer() {
+            Number::from_bigint(r.to_integer())
+        } else {
+            Number::Rational(r)
+        }
+    }
+
+    // Only meaningful for exact numbers.
+    fn to_rational(&self) -> BigRational {
+        match self {
+            Number::Int(n) => BigRational::from_integer(BigInt::from(*n)),
+            Number::Big(n) => BigRational::from_integer(n.clone()),
+            Number::Rational(r) => r.clone(),
+            Number::Float(x) => BigRational::from_float(*x).unwrap_or_else(BigRational::zero),
+        }
+    }
+
+    // Only meaningful for exact integers.
+    fn to_bigint(&self) -> BigInt {
+        match self {
+            Number::Int(n) => BigInt::from(*n),
+            Number::Big(n) => n.clone(),
+            _ => self.to_rational().to_integer(),
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(n) => *n as f64,
+            Number::Big(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Rational(r) => r.to_f64().unwrap_or(f64::NAN),
+            Number::Float(x) => *x,
+        }
+    }
+
+    /// The value as an index or count, if it is a non-negative exact integer.
+    pub fn to_usize(&self) -> Option<usize> {
+        match self {
+            Number::Int(n) => usize::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self {
+            Number::Int(_) | Number::Big(_) => true,
+            Number::Rational(_) => false,
+            Number::Float(x) => x.fract() == 0.0,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(n) => *n == 0,
+            Number::Big(_) | Number::Rational(_) => false,
+            Number::Float(x) => *x == 0.0,
+        }
+    }
+
+    /// NaN and the infinities have no exact counterpart.
+    pub fn to_exact(&self) -> Option<Number> {
+        match self {
+            Number::Float(x) => BigRational::from_float(*x).map(Number::from_rational),
+            _ => Some(self.clone()),
+        }
+    }
+
+    pub fn to_inexact(&self) -> Number {
+        Number::Float(self.to_f64())
+    }
+
+    // Applies the operation matching the most general representation of the
+    // two operands; inexactness is contagious.
+    fn binary_op(
+        &self,
+        other: &Number,
+        int_op: fn(i64, i64) -> Option<i64>,
+        big_op: fn(&BigInt, &BigInt) -> BigInt,
+        rational_op: fn(&BigRational, &BigRational) -> BigRational,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Number {
+        match (self, other) {
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                Number::Float(float_op(self.to_f64(), other.to_f64()))
+            }
+            (Number::Int(a), Number::Int(b)) => match int_op(*a, *b) {
+                Some(n) => Number::Int(n),
+                None => Number::from_bigint(big_op(&BigInt::from(*a), &BigInt::from(*b))),
+            },
+            (Number::Rational(_), _) | (_, Number::Rational(_)) => {
+                Number::from_rational(rational_op(&self.to_rational(), &other.to_rational()))
+            }
+            _ => Number::from_bigint(big_op(&self.to_bigint(), &other.to_bigint())),
+        }
+    }
+
+    /// `None` when dividing by zero, exact or not.
+    pub fn checked_div(&self, other: &Number) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        match (self, other) {
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                Some(Number::Float(self.to_f64() / other.to_f64()))
+            }
+            _ => Some(Number::from_rational(
+                self.to_rational() / other.to_rational(),
+            )),
+        }
+    }
+
+    pub fn abs(&self) -> Number {
+        match self {
+            Number::Int(n) => match n.checked_abs() {
+                Some(n) => Number::Int(n),
+                None => Number::from_bigint(BigInt::from(*n).abs()),
+            },
+            Number::Big(n) => Number::Big(n.abs()),
+            Number::Rational(r) => Number::Rational(r.abs()),
+            Number::Float(x) => Number::Float(x.abs()),
+        }
+    }
+
+    pub fn floor(&self) -> Number {
+        match self {
+            Number::Rational(r) => Number::from_rational(r.floor()),
+            Number::Float(x) => Number::Float(x.floor()),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn ceil(&self) -> Number {
+        match self {
+            Number::Rational(r) => Number::from_rational(r.ceil()),
+            Number::Float(x) => Number::Float(x.ceil()),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn trunc(&self) -> Number {
+        match self {
+            Number::Rational(r) => Number::from_rational(r.trunc()),
+            Number::Float(x) => Number::Float(x.trunc()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Rounds halfway cases to the even neighbour, as Scheme does.
+    pub fn round(&self) -> Number {
+        match self {
+            Number::Rational(r) => {
+                let floor = r.floor();
+                let half = BigRational::new(BigInt::one(), BigInt::from(2));
+                let rounded = match (r - &floor).cmp(&half) {
+                    Ordering::Less => floor,
+                    Ordering::Greater => floor + BigRational::one(),
+                    Ordering::Equal if floor.to_integer().is_even() => floor,
+                    Ordering::Equal => floor + BigRational::one(),
+                };
+                Number::from_rational(rounded)
+            }
+            Number::Float(x) => Number::Float(x.round_ties_even()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Stays exact for an exact base raised to an exact integer power.
+    /// `None` when an exact zero is raised to a negative power.
+    pub fn pow(&self, exponent: &Number) -> Option<Number> {
+        let exponent_int = match exponent {
+            Number::Int(n) if self.is_exact() => i32::try_from(*n).ok(),
+            _ => None,
+        };
+        match exponent_int {
+            Some(n) if n >= 0 => Some(Number::from_rational(num_traits::pow(
+                self.to_rational(),
+                n as usize,
+            ))),
+            Some(n) => Number::Int(1).checked_div(self).map(|reciprocal| {
+                Number::from_rational(num_traits::pow(
+                    reciprocal.to_rational(),
+                    n.unsigned_abs() as usize,
+                ))
+            }),
+            None => Some(Number::Float(self.to_f64().powf(exponent.to_f64()))),
+        }
+    }
+
+    /// Exact for exact perfect squares such as `16` or `4/9`.
+    pub fn sqrt(&self) -> Number {
+        if self.is_exact() && !self.to_rational().is_negative() {
+            let r = self.to_rational();
+            let (numer, denom) = (r.numer().sqrt(), r.denom().sqrt());
+            if &(&numer * &numer) == r.numer() && &(&denom * &denom) == r.denom() {
+                return Number::from_rational(BigRational::new(numer, denom));
+            }
+        }
+        Number::Float(self.to_f64().sqrt())
+    }
+}
+
+impl Add for &Number {
+    type Output = Number;
+
+    fn add(self, other: &Number) -> Number {
+        self.binary_op(
+            other,
+            i64::checked_add,
+            |a, b| a + b,
+            |a, b| a + b,
+            |a, b| a + b,
+        )
+    }
+}
+
+impl Sub for &Number {
+    type Output = Number;
+
+    fn sub(self, other: &Number) -> Number {
+        self.binary_op(
+            other,
+            i64::checked_sub,
+            |a, b| a - b,
+            |a, b| a - b,
+            |a, b| a - b,
+        )
+    }
+}
+
+impl Mul for &Number {
+    type Output = Number;
+
+    fn mul(self, other: &Number) -> Number {
+        self.binary_op(
+            other,
+            i64::checked_mul,
+            |a, b| a * b,
+            |a, b| a * b,
+            |a, b| a * b,
+        )
+    }
+}
+
+impl Neg for &Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        &Number::Int(0) - self
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a.partial_cmp(b),
+            (Number::Float(a), Number::Float(b)) => a.partial_cmp(b),
+            // Compare a float against an exact number exactly, unless it is
+            // NaN or infinite.
+            (Number::Float(x), _) if !x.is_finite() => x.partial_cmp(&other.to_f64()),
+            (_, Number::Float(x)) if !x.is_finite() => self.to_f64().partial_cmp(x),
+            _ => self.to_rational().partial_cmp(&other.to_rational()),
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(n: i64) -> Number {
+        Number::Int(n)
+    }
+}
+
+impl From<usize> for Number {
+    fn from(n: usize) -> Number {
+        match i64::try_from(n) {
+            Ok(n) => Number::Int(n),
+            Err(_) => Number::from_bigint(BigInt::from(n)),
+        }
+    }
+}
+
+impl From<f64> for Number {
+    fn from(x: f64) -> Number {
+        Number::Float(x)
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            Number::Big(n) => write!(f, "{}", n),
+            Number::Rational(r) => write!(f, "{}/{}", r.numer(), r.denom()),
+            // `{:?}` keeps the `.0` on integral floats, so they read back
+            // as inexact.
+            Number::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(token: &str) -> Number {
+        Number::parse(token).unwrap()
+    }
+
+    #[test]
+    fn overflow_promotes_to_big_and_back() {
+        let max = Number::from(i64::MAX);
+        let sum = &max + &Number::from(1i64);
+        assert!(matches!(sum, Number::Big(_)));
+        assert_eq!(sum.to_string(), "9223372036854775808");
+        assert!(matches!(&sum - &Number::from(1i64), Number::Int(i64::MAX)));
+        assert!(matches!(&max * &max, Number::Big(_)));
+        assert!(matches!(Number::from(i64::MIN).abs(), Number::Big(_)));
+        assert!(matches!(-&Number::from(i64::MIN), Number::Big(_)));
+    }
+
+    #[test]
+    fn division_stays_exact() {
+        let third = Number::from(1i64).checked_div(&Number::from(3i64)).unwrap();
+        assert!(matches!(third, Number::Rational(_)));
+        assert_eq!(third.to_string(), "1/3");
+        assert!(matches!(&third * &Number::from(3i64), Number::Int(1)));
+        assert_eq!((&num("1/2") + &num("1/3")).to_string(), "5/6");
+        assert!(matches!(num("4/2"), Number::Int(2)));
+        assert_eq!(Number::from(1i64).checked_div(&Number::from(0i64)), None);
+        assert_eq!(Number::from(1.0).checked_div(&Number::from(0.0)), None);
+    }
+
+    #[test]
+    fn inexactness_is_contagious() {
+        let sum = &num("1/2") + &Number::from(0.25);
+        assert!(!sum.is_exact());
+        assert_eq!(sum.to_string(), "0.75");
+        assert_eq!(num("1/2"), Number::from(0.5));
+        assert_eq!(Number::from(2.0).to_string(), "2.0");
+    }
+
+    #[test]
+    fn round_ties_to_even() {
+        assert_eq!(num("5/2").round().to_string(), "2");
+        assert_eq!(num("7/2").round().to_string(), "4");
+        assert_eq!(num("-5/2").round().to_string(), "-2");
+        assert_eq!(num("7/3").round().to_string(), "2");
+        assert_eq!(num("2.5").round().to_string(), "2.0");
+        assert_eq!(num("3.5").round().to_string(), "4.0");
+        assert_eq!(num("-0.5").round().to_string(), "-0.0");
+    }
+
+    #[test]
+    fn parse_rejects_non_numbers() {
+        assert!(matches!(num("123456789012345678901234"), Number::Big(_)));
+        assert_eq!(Number::parse("1/0"), None);
+        assert_eq!(Number::parse("1/-2"), None);
+        assert_eq!(Number::parse("inf"), None);
+        assert_eq!(Number::parse("-"), None);
+    }
+
+    #[test]
+    fn pow_and_sqrt_stay_exact_when_they_can() {
+        assert_eq!(
+            Number::from(2i64)
+                .pow(&Number::from(100i64))
+                .unwrap()
+                .to_string(),
+            "1267650600228229401496703205376"
+        );
+        assert_eq!(
+            Number::from(2i64)
+                .pow(&Number::from(-2i64))
+                .unwrap()
+                .to_string(),
+            "1/4"
+        );
+        assert_eq!(Number::from(0i64).pow(&Number::from(-1i64)), None);
+        assert_eq!(num("4/9").sqrt().to_string(), "2/3");
+        assert!(!Number::from(2i64).sqrt().is_exact());
+    }
+}
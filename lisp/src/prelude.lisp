@@ -0,0 +1,28 @@
+; Derived forms shipped with the interpreter. They are `syntax-rules` macros,
+; so the temporaries they introduce can't capture the caller's bindings, and
+; the caller's bindings can't change what the names they use refer to.
+
+(define-syntax when
+  (syntax-rules ()
+    ((_ test body ...) (if test (begin body ...)))))
+
+(define-syntax unless
+  (syntax-rules ()
+    ((_ test body ...) (if test nil (begin body ...)))))
+
+; `(while test body ...)` evaluates the body for as long as test holds.
+(define-syntax while
+  (syntax-rules ()
+    ((_ test body ...)
+     (let loop ()
+       (if test
+           (begin body ... (loop)))))))
+
+; `(for (i from to) body ...)` evaluates the body with i bound to each
+; integer from `from` up to, but not including, `to`.
+(define-syntax for
+  (syntax-rules ()
+    ((_ (var from to) body ...)
+     (let loop ((var from) (limit to))
+       (if (< var limit)
+           (begin body ... (loop (+ var 1) limit)))))))
@@ -0,0 +1,749 @@
+use super::{env_get, gensym, Environment, Error, Expression};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+const ELLIPSIS: &str = "...";
+
+/// A macro defined with `(define-syntax name (syntax-rules (literal ...) (pattern template) ...))`.
+///
+/// Expansion is hygienic: names the template binds itself, such as the
+/// parameters of a `fn` it builds, are renamed within the form that binds
+/// them on every expansion, so they can't capture the caller's bindings; and
+/// the other names the template refers to are looked up where the macro was
+/// defined, so the caller's bindings can't capture them either.
+pub struct SyntaxRules {
+    literals: Vec<String>,
+    rules: Vec<Rule>,
+    // Where the macro was defined. It holds the macro itself, so only weakly.
+    env: Weak<RefCell<Environment>>,
+}
+
+struct Rule {
+    pattern: Expression,
+    template: Expression,
+    // The names the template defines with `def` and the like. They are the
+    // caller's to refer to, so they are neither renamed nor looked up where
+    // the macro was defined.
+    defined: Vec<String>,
+}
+
+/// A name a `syntax-rules` template refers to without binding it, as it
+/// appears in an expansion: it evaluates to the binding visible where the
+/// macro was defined, whatever the caller binds under the same name.
+pub struct Alias {
+    pub(crate) name: String,
+    env: Weak<RefCell<Environment>>,
+}
+
+impl Alias {
+    pub(crate) fn env(&self) -> Result<Rc<RefCell<Environment>>, Error> {
+        self.env.upgrade().ok_or_else(|| {
+            Error::type_error(format!(
+                "the environment {} was defined in no longer exists",
+                self.name
+            ))
+        })
+    }
+
+    pub(crate) fn env_ptr(&self) -> *const RefCell<Environment> {
+        self.env.as_ptr()
+    }
+}
+
+// What a pattern variable matched; variables under an ellipsis match a
+// sequence, one entry per repetition.
+#[derive(Clone)]
+enum Binding {
+    One(Expression),
+    Many(Vec<Binding>),
+}
+
+type Bindings = HashMap<String, Binding>;
+
+impl SyntaxRules {
+    /// Reads the `(syntax-rules ...)` form of a `define-syntax` evaluated in
+    /// `env`.
+    pub(crate) fn new(
+        spec: &Expression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Result<SyntaxRules, Error> {
+        let forms = match spec {
+            Expression::List(forms) => forms,
+            _ => return Err(Error::type_error("expected a syntax-rules form")),
+        };
+        let (literals, rules) = match forms.as_slice() {
+            [Expression::Symbol(s), Expression::List(literals), rules @ ..]
+                if s == "syntax-rules" =>
+            {
+                (literals, rules)
+            }
+            _ => {
+                return Err(Error::type_error(
+                    "expected (syntax-rules (literal ...) (pattern template) ...)",
+                ))
+            }
+        };
+        let literals = literals
+            .iter()
+            .map(|x| match x {
+                Expression::Symbol(s) => Ok(s.clone()),
+                _ => Err(Error::type_error(
+                    "expected syntax-rules literals to be symbols",
+                )),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let rules = rules
+            .iter()
+            .map(|rule| match rule {
+                Expression::List(rule) => match rule.as_slice() {
+                    [Expression::List(pattern), template] if !pattern.is_empty() => {
+                        // The macro keyword itself is never matched.
+                        Ok(Rule {
+                            pattern: Expression::List(pattern[1..].to_vec().into()),
+                            template: template.clone(),
+                            defined: defined_names(template),
+                        })
+                    }
+                    _ => Err(Error::type_error(
+                        "expected a syntax rule to be (pattern template)",
+                    )),
+                },
+                _ => Err(Error::type_error(
+                    "expected a syntax rule to be (pattern template)",
+                )),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let syntax_rules = SyntaxRules {
+            literals,
+            rules,
+            env: Rc::downgrade(env),
+        };
+        for rule in &syntax_rules.rules {
+            let vars = syntax_rules.pattern_vars(&rule.pattern);
+            for (i, var) in vars.iter().enumerate() {
+                if vars[..i].contains(var) {
+                    return Err(Error::parse(
+                        format!(
+                            "pattern variable {} appears more than once in a pattern",
+                            var
+                        ),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        Ok(syntax_rules)
+    }
+
+    /// Rewrites a use of the macro, given its unevaluated argument forms.
+    pub fn expand(&self, arg_forms: &[Expression]) -> Result<Expression, Error> {
+        let form = Expression::List(arg_forms.to_vec().into());
+        let env = self.env.upgrade();
+        for rule in &self.rules {
+            let mut bindings = Bindings::new();
+            if self.match_pattern(&rule.pattern, &form, &mut bindings) {
+                let scope = Scope {
+                    env: env.as_ref(),
+                    defined: &rule.defined,
+                    renames: HashMap::new(),
+                    quoting: Quoting::None,
+                };
+                return expand_template(&rule.template, &bindings, &scope);
+            }
+        }
+        Err(Error::parse(
+            format!("no syntax rule matches {}", form),
+            None,
+        ))
+    }
+
+    fn match_pattern(
+        &self,
+        pattern: &Expression,
+        form: &Expression,
+        bindings: &mut Bindings,
+    ) -> bool {
+        match pattern {
+            Expression::Symbol(s) if s == "_" => true,
+            Expression::Symbol(s) if self.literals.contains(s) => {
+                matches!(form, Expression::Symbol(f) if f == s)
+            }
+            Expression::Symbol(s) => {
+                bindings.insert(s.clone(), Binding::One(form.clone()));
+                true
+            }
+            Expression::List(patterns) => match form {
+                Expression::List(forms) => self.match_list(patterns, forms, bindings),
+                _ => false,
+            },
+            Expression::Vector(patterns) => match form {
+                Expression::Vector(forms) => self.match_list(patterns, forms, bindings),
+                _ => false,
+            },
+            Expression::MapLiteral(patterns) => match form {
+                Expression::MapLiteral(forms) => self.match_list(patterns, forms, bindings),
+                _ => false,
+            },
+            Expression::Nil => matches!(form, Expression::Nil),
+            Expression::Keyword(a) => matches!(form, Expression::Keyword(b) if a == b),
+            Expression::Bool(a) => matches!(form, Expression::Bool(b) if a == b),
+            Expression::Number(a) => matches!(form, Expression::Number(b) if a == b),
+            Expression::Str(a) => matches!(form, Expression::Str(b) if a == b),
+            _ => false,
+        }
+    }
+
+    fn match_list(
+        &self,
+        patterns: &[Expression],
+        forms: &[Expression],
+        bindings: &mut Bindings,
+    ) -> bool {
+        let ellipsis = patterns.iter().position(is_ellipsis);
+        let (before, repeated, after) = match ellipsis {
+            Some(idx) if idx > 0 => (
+                &patterns[..idx - 1],
+                Some(&patterns[idx - 1]),
+                &patterns[idx + 1..],
+            ),
+            _ => (patterns, None, &patterns[patterns.len()..]),
+        };
+        let fixed = before.len() + after.len();
+        match repeated {
+            None if forms.len() != fixed => return false,
+            Some(_) if forms.len() < fixed => return false,
+            _ => {}
+        }
+        let repeated_forms = &forms[before.len()..forms.len() - after.len()];
+        let after_forms = &forms[forms.len() - after.len()..];
+
+        let fixed_match = before
+            .iter()
+            .zip(forms)
+            .chain(after.iter().zip(after_forms))
+            .all(|(pattern, form)| self.match_pattern(pattern, form, bindings));
+        if !fixed_match {
+            return false;
+        }
+        if let Some(repeated) = repeated {
+            let vars = self.pattern_vars(repeated);
+            let mut seqs: Vec<Vec<Binding>> = vec![vec![]; vars.len()];
+            for form in repeated_forms {
+                let mut inner = Bindings::new();
+                if !self.match_pattern(repeated, form, &mut inner) {
+                    return false;
+                }
+                for (var, seq) in vars.iter().zip(seqs.iter_mut()) {
+                    match inner.remove(var) {
+                        Some(binding) => seq.push(binding),
+                        None => return false,
+                    }
+                }
+            }
+            for (var, seq) in vars.into_iter().zip(seqs) {
+                bindings.insert(var, Binding::Many(seq));
+            }
+        }
+        true
+    }
+
+    fn pattern_vars(&self, pattern: &Expression) -> Vec<String> {
+        match pattern {
+            Expression::Symbol(s) if s != "_" && s != ELLIPSIS && !self.literals.contains(s) => {
+                vec![s.clone()]
+            }
+            Expression::List(patterns) => {
+                patterns.iter().flat_map(|p| self.pattern_vars(p)).collect()
+            }
+            Expression::Vector(patterns) | Expression::MapLiteral(patterns) => {
+                patterns.iter().flat_map(|p| self.pattern_vars(p)).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+fn is_ellipsis(exp: &Expression) -> bool {
+    matches!(exp, Expression::Symbol(s) if s == ELLIPSIS)
+}
+
+// How the part of a template being expanded is quoted. Symbols in quoted
+// data are left as they are, except under `unquote` in a quasiquoted one.
+#[derive(Clone, Copy)]
+enum Quoting {
+    None,
+    Quote,
+    Quasiquote,
+}
+
+// What the symbols of a template expand to, at some point within it.
+#[derive(Clone)]
+struct Scope<'a> {
+    env: Option<&'a Rc<RefCell<Environment>>>,
+    defined: &'a [String],
+    // The fresh names of the template's own binders in scope here.
+    renames: HashMap<String, String>,
+    quoting: Quoting,
+}
+
+impl Scope<'_> {
+    // The scope within a form that binds `names`. Those the template spells
+    // out itself, rather than taking them from the macro's arguments, get
+    // fresh names.
+    fn binding(&self, names: Vec<String>, bindings: &Bindings) -> Self {
+        let mut scope = self.clone();
+        for name in names {
+            if name != ELLIPSIS && !bindings.contains_key(&name) {
+                let renamed = gensym(&name);
+                scope.renames.insert(name, renamed);
+            }
+        }
+        scope
+    }
+
+    fn quoted(&self, quoting: Quoting) -> Self {
+        Scope {
+            quoting,
+            ..self.clone()
+        }
+    }
+}
+
+fn expand_template(
+    template: &Expression,
+    bindings: &Bindings,
+    scope: &Scope,
+) -> Result<Expression, Error> {
+    match template {
+        Expression::Symbol(s) => expand_symbol(s, bindings, scope),
+        Expression::Vector(templates) => Ok(Expression::Vector(Rc::new(expand_all(
+            templates, bindings, scope,
+        )?))),
+        Expression::MapLiteral(templates) => {
+            let items = expand_all(templates, bindings, scope)?;
+            if !items.len().is_multiple_of(2) {
+                return Err(Error::parse(
+                    "expected a map template to expand to a value for every key",
+                    None,
+                ));
+            }
+            Ok(Expression::MapLiteral(Rc::new(items)))
+        }
+        Expression::List(templates) => Ok(Expression::List(
+            expand_list(templates, bindings, scope)?.into(),
+        )),
+        _ => Ok(template.clone()),
+    }
+}
+
+fn expand_symbol(s: &str, bindings: &Bindings, scope: &Scope) -> Result<Expression, Error> {
+    match bindings.get(s) {
+        Some(Binding::One(exp)) => return Ok(exp.clone()),
+        Some(Binding::Many(_)) => {
+            return Err(Error::parse(
+                format!(
+                    "pattern variable {} is missing an ellipsis in the template",
+                    s
+                ),
+                None,
+            ))
+        }
+        None => {}
+    }
+    if let Quoting::None = scope.quoting {
+        if let Some(renamed) = scope.renames.get(s) {
+            return Ok(Expression::Symbol(renamed.clone()));
+        }
+        // Names nothing binds where the macro was defined, such as `else`,
+        // are left for the caller.
+        match scope.env {
+            Some(env) if !scope.defined.iter().any(|d| d == s) && env_get(s, env).is_some() => {
+                return Ok(Expression::Alias(Rc::new(Alias {
+                    name: s.to_string(),
+                    env: Rc::downgrade(env),
+                })))
+            }
+            _ => {}
+        }
+    }
+    Ok(Expression::Symbol(s.to_string()))
+}
+
+// Expands the items of a list template, which bind names in the scope of
+// some of the others if it is a binding form such as `let`.
+fn expand_list(
+    templates: &[Expression],
+    bindings: &Bindings,
+    scope: &Scope,
+) -> Result<Vec<Expression>, Error> {
+    let keyword = match templates.first() {
+        Some(Expression::Symbol(s))
+            if !bindings.contains_key(s) && !scope.renames.contains_key(s) =>
+        {
+            s.as_str()
+        }
+        _ => "",
+    };
+    match scope.quoting {
+        Quoting::None => {}
+        Quoting::Quasiquote if keyword == "unquote" || keyword == "unquote-splicing" => {
+            return expand_all(templates, bindings, &scope.quoted(Quoting::None))
+        }
+        Quoting::Quote | Quoting::Quasiquote => return expand_all(templates, bindings, scope),
+    }
+    let head = || expand_template(&templates[0], bindings, scope);
+    let rest = &templates[1.min(templates.len())..];
+    let mut res = vec![];
+    match (keyword, rest) {
+        ("quote", _) => {
+            res.push(head()?);
+            res.extend(expand_all(rest, bindings, &scope.quoted(Quoting::Quote))?);
+        }
+        ("quasiquote", _) => {
+            res.push(head()?);
+            res.extend(expand_all(
+                rest,
+                bindings,
+                &scope.quoted(Quoting::Quasiquote),
+            )?);
+        }
+        ("fn", [params @ Expression::List(names), body @ ..]) => {
+            let inner = scope.binding(param_names(names), bindings);
+            res.push(head()?);
+            res.push(expand_template(params, bindings, &inner)?);
+            res.extend(expand_all(body, bindings, &inner)?);
+        }
+        ("catch", [Expression::Symbol(var), ..]) => {
+            let inner = scope.binding(vec![var.clone()], bindings);
+            res.push(head()?);
+            res.extend(expand_all(rest, bindings, &inner)?);
+        }
+        // `(let loop ((i 0)) ...)` binds the loop name as well as `i` in its
+        // body. A pattern variable in its place may be the binding list.
+        ("let", [Expression::Symbol(name), Expression::List(lets), body @ ..])
+            if !bindings.contains_key(name) =>
+        {
+            let mut names = vec![name.clone()];
+            names.extend(binding_names(lets));
+            let inner = scope.binding(names, bindings);
+            res.push(head()?);
+            res.push(expand_template(&rest[0], bindings, &inner)?);
+            res.push(Expression::List(
+                expand_items(lets, bindings, |binding, bindings| {
+                    expand_binding(binding, bindings, &inner, scope)
+                })?
+                .into(),
+            ));
+            res.extend(expand_all(body, bindings, &inner)?);
+        }
+        ("let", [Expression::List(lets), body @ ..])
+        | ("letrec", [Expression::List(lets), body @ ..]) => {
+            let inner = scope.binding(binding_names(lets), bindings);
+            // Only `letrec` evaluates the values in the scope of the names.
+            let init_scope = if keyword == "let" { scope } else { &inner };
+            res.push(head()?);
+            res.push(Expression::List(
+                expand_items(lets, bindings, |binding, bindings| {
+                    expand_binding(binding, bindings, &inner, init_scope)
+                })?
+                .into(),
+            ));
+            res.extend(expand_all(body, bindings, &inner)?);
+        }
+        // Each name of a `let*` is in the scope of the values that follow.
+        ("let*", [Expression::List(lets), body @ ..]) => {
+            let mut inner = scope.clone();
+            res.push(head()?);
+            res.push(Expression::List(
+                expand_items(lets, bindings, |binding, bindings| {
+                    let outer = inner.clone();
+                    inner = outer.binding(binding_names(std::slice::from_ref(binding)), bindings);
+                    expand_binding(binding, bindings, &inner, &outer)
+                })?
+                .into(),
+            ));
+            res.extend(expand_all(body, bindings, &inner)?);
+        }
+        _ => return expand_all(templates, bindings, scope),
+    }
+    Ok(res)
+}
+
+// Expands a `(name value)` pair of a binding list, the name in the scope of
+// the binding form and the value in `value_scope`.
+fn expand_binding(
+    binding: &Expression,
+    bindings: &Bindings,
+    scope: &Scope,
+    value_scope: &Scope,
+) -> Result<Expression, Error> {
+    match binding {
+        Expression::List(pair) => match pair.as_slice() {
+            [name, value] if !is_ellipsis(value) => Ok(Expression::List(
+                vec![
+                    expand_template(name, bindings, scope)?,
+                    expand_template(value, bindings, value_scope)?,
+                ]
+                .into(),
+            )),
+            _ => expand_template(binding, bindings, scope),
+        },
+        _ => expand_template(binding, bindings, scope),
+    }
+}
+
+fn expand_all(
+    templates: &[Expression],
+    bindings: &Bindings,
+    scope: &Scope,
+) -> Result<Vec<Expression>, Error> {
+    expand_items(templates, bindings, |template, bindings| {
+        expand_template(template, bindings, scope)
+    })
+}
+
+// Expands each of `templates` with `expand`, once per repetition for those
+// followed by an ellipsis.
+fn expand_items<F>(
+    templates: &[Expression],
+    bindings: &Bindings,
+    mut expand: F,
+) -> Result<Vec<Expression>, Error>
+where
+    F: FnMut(&Expression, &Bindings) -> Result<Expression, Error>,
+{
+    let mut res: Vec<Expression> = vec![];
+    let mut iter = templates.iter().peekable();
+    while let Some(item) = iter.next() {
+        if iter.peek().is_some_and(|next| is_ellipsis(next)) {
+            iter.next();
+            for item_bindings in repetitions(item, bindings)? {
+                res.push(expand(item, &item_bindings)?);
+            }
+        } else {
+            res.push(expand(item, bindings)?);
+        }
+    }
+    Ok(res)
+}
+
+// The names the template defines with `def` and the like, wherever they are
+// in it.
+fn defined_names(template: &Expression) -> Vec<String> {
+    let templates = match template {
+        Expression::List(templates) => templates,
+        _ => return vec![],
+    };
+    match templates.as_slice() {
+        [Expression::Symbol(s), ..] if s == "quote" => vec![],
+        [Expression::Symbol(s), Expression::Symbol(name), ..]
+            if s == "def" || s == "defmacro" || s == "define-syntax" =>
+        {
+            let mut names = vec![name.clone()];
+            names.extend(templates[2..].iter().flat_map(defined_names));
+            names
+        }
+        _ => templates.iter().flat_map(defined_names).collect(),
+    }
+}
+
+// The names bound by a `fn` parameter list such as `(a (b 1) & rest)`; the
+// default forms only refer to names.
+fn param_names(params: &[Expression]) -> Vec<String> {
+    params
+        .iter()
+        .filter_map(|param| match param {
+            Expression::Symbol(s) if s != "&" && s != "." && s != "&key" => Some(s.clone()),
+            Expression::List(pair) => match pair.first() {
+                Some(Expression::Symbol(s)) => Some(s.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+// The names bound by a `let` binding list such as `((a 1) (b 2))`.
+fn binding_names(bindings: &[Expression]) -> Vec<String> {
+    bindings
+        .iter()
+        .filter_map(|binding| match binding {
+            Expression::List(pair) => match pair.first() {
+                Some(Expression::Symbol(s)) => Some(s.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+fn template_symbols(template: &Expression) -> Vec<String> {
+    match template {
+        Expression::Symbol(s) => vec![s.clone()],
+        Expression::List(templates) => templates.iter().flat_map(template_symbols).collect(),
+        Expression::Vector(templates) | Expression::MapLiteral(templates) => {
+            templates.iter().flat_map(template_symbols).collect()
+        }
+        _ => vec![],
+    }
+}
+
+// The bindings to expand `template` with on each repetition of an ellipsis
+// that follows it, driven by the sequence variables it mentions.
+fn repetitions(template: &Expression, bindings: &Bindings) -> Result<Vec<Bindings>, Error> {
+    let mut seqs: Vec<(String, &Vec<Binding>)> = vec![];
+    for s in template_symbols(template) {
+        if let Some(Binding::Many(seq)) = bindings.get(&s) {
+            if !seqs.iter().any(|(var, _)| *var == s) {
+                seqs.push((s, seq));
+            }
+        }
+    }
+    let len = match seqs.first() {
+        Some((_, seq)) => seq.len(),
+        None => {
+            return Err(Error::parse(
+                "no pattern variable with an ellipsis before `...` in the template",
+                None,
+            ))
+        }
+    };
+    if seqs.iter().any(|(_, seq)| seq.len() != len) {
+        return Err(Error::parse(
+            "pattern variables under the same ellipsis matched different lengths",
+            None,
+        ));
+    }
+    Ok((0..len)
+        .map(|i| {
+            let mut item_bindings = bindings.clone();
+            for (var, seq) in &seqs {
+                item_bindings.insert(var.clone(), seq[i].clone());
+            }
+            item_bindings
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_env, parse, tokenize};
+
+    fn read(source: &str) -> Expression {
+        parse(&tokenize(source).unwrap()).unwrap().0
+    }
+
+    // Expands the use `form` of the macro `spec` defines.
+    fn expand(spec: &str, form: &str) -> Result<Expression, Error> {
+        let env = default_env();
+        let rules = SyntaxRules::new(&read(spec), &env)?;
+        match read(form) {
+            Expression::List(form) => rules.expand(&form[1..]),
+            _ => panic!("expected a list form"),
+        }
+    }
+
+    fn items(exp: &Expression) -> &[Expression] {
+        match exp {
+            Expression::List(list) => list,
+            _ => panic!("expected a list, got {}", exp),
+        }
+    }
+
+    fn symbol(exp: &Expression) -> &str {
+        match exp {
+            Expression::Symbol(s) => s,
+            _ => panic!("expected a symbol, got {}", exp),
+        }
+    }
+
+    #[test]
+    fn ellipsis_repeats_the_matched_forms() {
+        let spec = "(syntax-rules () ((_ x ...) (quote (x ... end))))";
+        assert_eq!(
+            expand(spec, "(m a b c)").unwrap().to_string(),
+            "(quote (a b c end))"
+        );
+        assert_eq!(expand(spec, "(m)").unwrap().to_string(), "(quote (end))");
+    }
+
+    #[test]
+    fn nested_ellipses_follow_the_nesting() {
+        let spec = "(syntax-rules () ((_ (a b ...) ...) (quote ((b ... a) ...))))";
+        assert_eq!(
+            expand(spec, "(m (1 2 3) (4) (5 6))").unwrap().to_string(),
+            "(quote ((2 3 1) (4) (6 5)))"
+        );
+    }
+
+    #[test]
+    fn ellipsis_errors() {
+        let spec = "(syntax-rules () ((_ (a ...) (b ...)) (quote ((a b) ...))))";
+        assert!(expand(spec, "(m (1 2) (3 4))").is_ok());
+        assert!(expand(spec, "(m (1 2) (3))").is_err());
+        let spec = "(syntax-rules () ((_ x ...) (quote x)))";
+        assert!(expand(spec, "(m 1 2)").is_err());
+        let spec = "(syntax-rules () ((_ x x) x))";
+        assert!(expand(spec, "(m 1 2)").is_err());
+    }
+
+    #[test]
+    fn literals_must_match_themselves() {
+        let spec = "(syntax-rules (=>) ((_ a => b) (quote (a b))))";
+        assert_eq!(
+            expand(spec, "(m 1 => 2)").unwrap().to_string(),
+            "(quote (1 2))"
+        );
+        assert!(expand(spec, "(m 1 -> 2)").is_err());
+    }
+
+    #[test]
+    fn binders_the_template_introduces_are_renamed() {
+        let spec = "(syntax-rules () ((_ e) (let ((tmp e)) (quote tmp))))";
+        let exp = expand(spec, "(m 1)").unwrap();
+        let binding = items(&items(&exp)[1])[0].clone();
+        let renamed = symbol(&items(&binding)[0]);
+        assert!(renamed.starts_with("tmp__"), "{}", renamed);
+        // Quoted names are data, and stay as they are.
+        assert_eq!(items(&exp)[2].to_string(), "(quote tmp)");
+
+        let other = expand(spec, "(m 1)").unwrap();
+        assert_ne!(items(&items(&other)[1])[0], binding);
+    }
+
+    #[test]
+    fn binders_from_the_arguments_are_kept() {
+        let spec = "(syntax-rules () ((_ v e) (let ((v e)) v)))";
+        assert_eq!(
+            expand(spec, "(m x 1)").unwrap().to_string(),
+            "(let ((x 1)) x)"
+        );
+    }
+
+    #[test]
+    fn renames_only_apply_within_the_binding_form() {
+        let spec = "(syntax-rules () ((_ x) (begin (def y x) (let ((y 0)) y))))";
+        let exp = expand(spec, "(m 5)").unwrap();
+        assert_eq!(items(&exp)[1].to_string(), "(def y 5)");
+        let inner = items(&items(&exp)[2]);
+        let binder = symbol(&items(&items(&inner[1])[0])[0]);
+        assert_ne!(binder, "y");
+        assert_eq!(symbol(&inner[2]), binder);
+    }
+
+    #[test]
+    fn free_names_refer_to_the_definition_environment() {
+        let spec = "(syntax-rules () ((_ x) (cond (x (+ x 1)) (else 0))))";
+        let exp = expand(spec, "(m +)").unwrap();
+        let clause = items(&items(&exp)[1]);
+        // The argument is the caller's `+`, the template's is an alias.
+        assert_eq!(symbol(&clause[0]), "+");
+        assert!(matches!(&items(&clause[1])[0], Expression::Alias(_)));
+        // Names with no binding there, such as special forms, stay symbols.
+        assert_eq!(symbol(&items(&exp)[0]), "cond");
+        assert_eq!(symbol(&items(&items(&exp)[2])[0]), "else");
+    }
+}
@@ -0,0 +1,118 @@
+use lisp::Interpreter;
+
+// The printed value of the last form in `source`.
+fn eval(source: &str) -> String {
+    Interpreter::new().eval_str(source).unwrap().to_string()
+}
+
+fn error_message(source: &str) -> String {
+    Interpreter::new().eval_str(source).unwrap_err().message()
+}
+
+#[test]
+fn let_forms_bind_each_name_once() {
+    assert_eq!(eval("(let ((x 1) (y 2)) (+ x y))"), "3");
+    assert_eq!(eval("(let* ((x 1) (x (+ x 1))) x)"), "2");
+    assert_eq!(
+        error_message("(let ((x 1) (x 2)) x)"),
+        "x is bound more than once in let"
+    );
+    assert_eq!(
+        error_message("(letrec ((f 1) (f 2)) f)"),
+        "f is bound more than once in letrec"
+    );
+    assert_eq!(
+        error_message("(let loop ((i 0) (i 1)) i)"),
+        "i is bound more than once in let"
+    );
+}
+
+#[test]
+fn rest_optional_and_keyword_parameters() {
+    assert_eq!(eval("((fn (a & rest) (list a rest)) 1 2 3)"), "(1 (2 3))");
+    assert_eq!(eval("((fn (a & rest) (list a rest)) 1)"), "(1 ())");
+    assert_eq!(eval("((fn (a . rest) rest) 1 2)"), "(2)");
+    // Defaults can refer to the parameters before them.
+    assert_eq!(
+        eval("((fn (a (b 2) (c (+ a b))) (list a b c)) 1)"),
+        "(1 2 3)"
+    );
+    assert_eq!(eval("((fn (a (b 2)) (list a b)) 1 5)"), "(1 5)");
+    assert_eq!(
+        eval("((fn (a &key (b 2) c) (list a b c)) 1 :c 3)"),
+        "(1 2 3)"
+    );
+}
+
+#[test]
+fn calls_must_fit_the_parameters() {
+    assert_eq!(
+        error_message("((fn (a (b 1)) a) 1 2 3)"),
+        "expected 1 to 2 arguments, got 3"
+    );
+    assert_eq!(
+        error_message("((fn (a & rest) a))"),
+        "expected at least 1 arguments, got 0"
+    );
+    assert_eq!(
+        error_message("((fn (&key a) a) :b 1)"),
+        "unexpected keyword argument :b"
+    );
+    assert_eq!(
+        error_message("((fn (&key a) a) :a)"),
+        "missing a value for :a"
+    );
+    assert_eq!(
+        error_message("(fn ((a 1) b) a)"),
+        "required parameter b follows an optional one"
+    );
+    assert_eq!(
+        error_message("(fn (a & b c) a)"),
+        "expected a single symbol after & in the argument list"
+    );
+    assert_eq!(
+        error_message("(fn (a (b 1) &key c) a)"),
+        "keyword parameters can't be combined with optional or rest parameters"
+    );
+}
+
+#[test]
+fn parameters_have_distinct_names() {
+    assert_eq!(
+        error_message("(fn (a a) a)"),
+        "a is bound more than once in an argument list"
+    );
+    assert_eq!(
+        error_message("(fn (a (a 1)) a)"),
+        "a is bound more than once in an argument list"
+    );
+    assert_eq!(
+        error_message("(fn (a & a) a)"),
+        "a is bound more than once in an argument list"
+    );
+    assert_eq!(
+        error_message("(fn (a &key (b 1) a) a)"),
+        "a is bound more than once in an argument list"
+    );
+}
+
+#[test]
+fn closures_capture_their_environment() {
+    let counters = "(def make-counter (fn () (let ((n 0)) (fn () (set! n (+ n 1)) n))))
+                    (def c1 (make-counter))
+                    (def c2 (make-counter))
+                    (c1) (c1)
+                    (list (c1) (c2))";
+    assert_eq!(eval(counters), "(3 1)");
+    let partial = "(def partial (fn (f & xs) (fn (& ys) (apply f (append xs ys)))))
+                   ((partial + 1 2) 3 4)";
+    assert_eq!(eval(partial), "10");
+    let adders = "(def adders (map (fn (n) (fn (x) (+ x n))) (list 1 10)))
+                  (list ((car adders) 1) ((car (cdr adders)) 1))";
+    assert_eq!(eval(adders), "(2 11)");
+    // A closure sees later changes to the bindings it captured.
+    assert_eq!(
+        eval("(def x 1) (def get-x (fn () x)) (set! x 2) (get-x)"),
+        "2"
+    );
+}
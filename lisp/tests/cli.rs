@@ -0,0 +1,92 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn lisp(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lisp"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// Writes `source` to a file of its own in the temporary directory.
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lisp-cli-{}-{}", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn files_run_every_form() {
+    let path = script(
+        "ok.lisp",
+        "#!/usr/bin/env lisp\n(def x 2)\n(println (* x 3))\n(+ x 1)\n",
+    );
+    let output = lisp(&[path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    // The value of the last form is not printed.
+    assert_eq!(stdout(&output), "6\n");
+}
+
+#[test]
+fn files_that_fail_exit_with_1() {
+    let path = script("fail.lisp", "(println 1)\n(car \"a\")\n(println 2)\n");
+    let output = lisp(&[path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains(&format!("--> {}:2:1", path.display())));
+
+    let output = lisp(&["/nonexistent/file.lisp"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("lisp: cannot read /nonexistent/file.lisp"));
+}
+
+#[test]
+fn programs_can_come_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lisp"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"(println (+ 1 2))")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn expressions_print_their_last_value() {
+    let output = lisp(&["-e", "(def x 2) (* x 21)"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "42\n");
+
+    let output = lisp(&["-e", "(+ 1 (car nil))"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("--> -e:1:6"));
+}
+
+#[test]
+fn bad_arguments_exit_with_2() {
+    let output = lisp(&["-e"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("usage: lisp"));
+    assert_eq!(lisp(&["--help"]).status.code(), Some(0));
+}
@@ -0,0 +1,102 @@
+use lisp::Interpreter;
+
+// The printed value of the last form in `source`.
+fn eval(source: &str) -> String {
+    Interpreter::new().eval_str(source).unwrap().to_string()
+}
+
+fn error_message(source: &str) -> String {
+    Interpreter::new().eval_str(source).unwrap_err().message()
+}
+
+#[test]
+fn literals_evaluate_their_items() {
+    assert_eq!(eval("[1 (+ 1 1) :three]"), "[1 2 :three]");
+    assert_eq!(eval("{:sum (+ 1 2) (list 1) [4]}"), "{:sum 3 (1) [4]}");
+    assert_eq!(eval("(def k :z) {k 1}"), "{:z 1}");
+    assert_eq!(eval("`[1 ,(+ 1 1) ,@(list 3 4)]"), "[1 2 3 4]");
+    assert_eq!(eval("`{:a ,(+ 1 2)}"), "{:a 3}");
+}
+
+#[test]
+fn map_literal_keys_must_differ() {
+    let duplicate = "duplicate key :a in a map literal";
+    assert_eq!(error_message("{:a 1 :a 2}"), duplicate);
+    assert_eq!(error_message("'{:a 1 :a 2}"), duplicate);
+    assert_eq!(error_message("(read-string \"{:a 1 :a 2}\")"), duplicate);
+    assert_eq!(error_message("(def k :a) {k 1 :a 2}"), duplicate);
+    // The same form may evaluate to different keys.
+    assert_eq!(eval("(length (keys {(gensym) 1 (gensym) 2}))"), "2");
+    assert_eq!(
+        error_message("{:a}"),
+        "expected a map literal to have a value for every key"
+    );
+}
+
+#[test]
+fn quoted_and_read_maps_are_maps() {
+    assert_eq!(eval("(get '{:a (x y)} :a)"), "(x y)");
+    assert_eq!(eval("(map? (nth '(1 {:a 1}) 1))"), "true");
+    assert_eq!(eval("(equal? '{:a 1} {:a 1})"), "true");
+    assert_eq!(eval("(keys (dissoc '{:a 1 :b 2} :a))"), "(:b)");
+    assert_eq!(
+        eval("(get (read-string \"{:b [1 {:c 2}]}\") :b)"),
+        "[1 {:c 2}]"
+    );
+}
+
+#[test]
+fn vector_builtins() {
+    assert_eq!(eval("(vector 1 2)"), "[1 2]");
+    assert_eq!(eval("(get [1 2 3] 1)"), "2");
+    assert_eq!(eval("(get [1 2] 5)"), "nil");
+    assert_eq!(eval("(assoc [1 2] 0 :x)"), "[:x 2]");
+    assert_eq!(eval("(length [1 2])"), "2");
+    assert_eq!(eval("(map (fn (x) (* x x)) [1 2 3])"), "(1 4 9)");
+    assert_eq!(eval("(list->vector (vector->list [1 2]))"), "[1 2]");
+    assert_eq!(eval("(vector? [1])"), "true");
+    assert_eq!(eval("(equal? [1 [2]] (vector 1 (vector 2)))"), "true");
+}
+
+#[test]
+fn map_builtins() {
+    assert_eq!(eval("(get {:a 1} :a)"), "1");
+    assert_eq!(eval("(get {:a 1} :b)"), "nil");
+    assert_eq!(eval("(get {:a 1} :b 0)"), "0");
+    assert_eq!(eval("(get {(list 1 2) :l} (list 1 2))"), ":l");
+    assert_eq!(eval("(contains? {:a nil} :a)"), "true");
+    assert_eq!(eval("(assoc {:a 1} :b 2 :a 3)"), "{:a 3 :b 2}");
+    assert_eq!(eval("(dissoc {:a 1 :b 2} :a)"), "{:b 2}");
+    assert_eq!(eval("(keys {:a 1 :b 2})"), "(:a :b)");
+    assert_eq!(eval("(vals {:a 1 :b 2})"), "(1 2)");
+    assert_eq!(eval("(update {:a 1} :a (fn (x) (+ x 1)))"), "{:a 2}");
+    assert_eq!(eval("(hash-map :a 1 :a 2)"), "{:a 2}");
+    assert_eq!(eval("(equal? {:a 1 :b 2} {:b 2 :a 1})"), "true");
+    assert_eq!(
+        error_message("(assoc {} (fn (x) x) 1)"),
+        "#<fn (x)> can't be a map key"
+    );
+}
+
+#[test]
+fn keys_that_are_equal_are_the_same_key() {
+    assert_eq!(eval("(equal? 0.0 (* -1.0 0))"), "true");
+    assert_eq!(eval("(get {0.0 :x} (* -1.0 0))"), ":x");
+    assert_eq!(eval("(get {[-0.0] :x} [0.0])"), ":x");
+    assert_eq!(eval("(length (keys (assoc {0.0 1} -0.0 2)))"), "1");
+    // Exact and inexact numbers are never `equal?`, so they stay apart.
+    assert_eq!(eval("(length (keys {1 :a 1.0 :b}))"), "2");
+    assert_eq!(eval("(get {\"-0.0\" 1} \"0.0\")"), "nil");
+}
+
+#[test]
+fn range_counts_its_elements_first() {
+    assert_eq!(eval("(range 4)"), "(0 1 2 3)");
+    assert_eq!(eval("(range 5 0 -2)"), "(5 3 1)");
+    assert_eq!(eval("(range 0 1 0.25)"), "(0 0.25 0.5 0.75)");
+    assert_eq!(eval("(range 3 1)"), "()");
+    assert_eq!(
+        error_message("(range 1000000000000)"),
+        "range: 1000000000000 elements are too many"
+    );
+}
@@ -0,0 +1,120 @@
+use lisp::{is_incomplete, render_error, Interpreter};
+
+// The printed value of the last form in `source`, which can record the order
+// things happen in with `(note x)`, which pushes `x` onto the array `log`.
+fn eval(source: &str) -> String {
+    let lisp = Interpreter::new();
+    lisp.eval_str("(def log (array)) (def note (fn (x) (array-push! log x)))")
+        .unwrap();
+    lisp.eval_str(source).unwrap().to_string()
+}
+
+// The rendered error that evaluating `source` fails with.
+fn rendered(source: &str) -> String {
+    let e = Interpreter::new().eval_str(source).unwrap_err();
+    render_error(&e, "test", source)
+}
+
+#[test]
+fn errors_point_at_the_failing_form() {
+    assert_eq!(
+        rendered("(* 2 (+ 1 \"a\"))"),
+        "type error: expected a number, got \"a\"
+ --> test:1:6
+  |
+1 | (* 2 (+ 1 \"a\"))
+  |      ^^^^^^^^^"
+    );
+    assert_eq!(
+        rendered("(def x 1)\n(+ x\n   (car \"a\"))"),
+        "type error: expected a list, got \"a\"
+ --> test:3:4
+  |
+3 |    (car \"a\"))
+  |    ^^^^^^^^^"
+    );
+    assert_eq!(
+        rendered("(+ 1 (undefined-fn 2))"),
+        "unbound symbol `undefined-fn`
+ --> test:1:7
+  |
+1 | (+ 1 (undefined-fn 2))
+  |       ^^^^^^^^^^^^"
+    );
+}
+
+#[test]
+fn spans_across_lines_underline_the_rest_of_the_first_line() {
+    assert_eq!(
+        rendered("(+ 1\n   \"a\")"),
+        "type error: expected a number, got \"a\"
+ --> test:1:1
+  |
+1 | (+ 1
+  | ^^^^"
+    );
+}
+
+#[test]
+fn unfinished_forms_are_incomplete() {
+    assert!(is_incomplete("(+ 1"));
+    assert!(is_incomplete("(def s \"abc"));
+    assert!(is_incomplete("(list [1 {:a"));
+    assert!(!is_incomplete("(+ 1 2)"));
+    assert!(!is_incomplete("(+ 1 2))"));
+    assert!(!is_incomplete(""));
+    assert_eq!(
+        rendered("(+ 1"),
+        "parse error: could not find closing `)`
+ --> test:1:1
+  |
+1 | (+ 1
+  | ^"
+    );
+}
+
+#[test]
+fn try_runs_the_handler_and_then_the_cleanup() {
+    assert_eq!(
+        eval(
+            "(def res (try (begin (note 1) (throw \"boom\") (note 2))
+                          (catch e (note e) 10)
+                          (finally (note 3))))
+             (list res log)"
+        ),
+        "(10 #<array 1 \"boom\" 3>)"
+    );
+    // Without an error only the body and the cleanup run, and the body gives
+    // the value.
+    assert_eq!(
+        eval("(list (try (note 1) 5 (catch e (note e)) (finally (note 3) 6)) log)"),
+        "(5 #<array 1 3>)"
+    );
+}
+
+#[test]
+fn errors_leave_try_after_the_cleanup() {
+    // An error without a handler, or one the handler raises, propagates
+    // once the cleanup has run.
+    assert_eq!(
+        eval("(try (try (throw 1) (finally (note \"cleanup\"))) (catch e (note e))) log"),
+        "#<array \"cleanup\" 1>"
+    );
+    assert_eq!(
+        eval(
+            "(try (try (throw 1) (catch e (throw (+ e 1))) (finally (note \"cleanup\")))
+                  (catch e (note e)))
+             log"
+        ),
+        "#<array \"cleanup\" 2>"
+    );
+    assert_eq!(
+        eval("(try (try (note 1) (finally (throw \"inner\"))) (catch e (note e))) log"),
+        "#<array 1 \"inner\">"
+    );
+    // Errors the interpreter raises are caught as error values.
+    assert_eq!(
+        eval("(try (car 1) (catch e (list (error-kind e) (error-message e))))"),
+        "(type \"expected a list, got 1\")"
+    );
+}
@@ -0,0 +1,200 @@
+use lisp::{Error, Expression, Interpreter, Number};
+
+fn int(n: i64) -> Expression {
+    Expression::Number(Number::from(n))
+}
+
+fn error_kind(lisp: &Interpreter, source: &str) -> &'static str {
+    match lisp.eval_str(source) {
+        Ok(exp) => panic!("expected {} to fail, got {}", source, exp),
+        Err(e) => e.kind_name(),
+    }
+}
+
+#[test]
+fn eval_str_returns_the_last_value() {
+    let lisp = Interpreter::new();
+    assert_eq!(lisp.eval_str("(def x 2) (* x 21)").unwrap(), int(42));
+    assert_eq!(lisp.eval_str("").unwrap(), Expression::Nil);
+    assert_eq!(lisp.eval_str("; only a comment").unwrap(), Expression::Nil);
+}
+
+#[test]
+fn globals_persist_across_calls() {
+    let lisp = Interpreter::new();
+    lisp.eval_str("(def square (fn (x) (* x x)))").unwrap();
+    assert_eq!(lisp.eval_str("(square 7)").unwrap(), int(49));
+    lisp.define("limit", int(10));
+    assert_eq!(lisp.eval_str("(+ limit 1)").unwrap(), int(11));
+    assert_eq!(lisp.get("limit"), Some(int(10)));
+    assert_eq!(lisp.get("missing"), None);
+}
+
+#[test]
+fn define_fn_converts_arguments_and_results() {
+    let lisp = Interpreter::new();
+    lisp.define_fn("clamp", |x: f64, lo: f64, hi: f64| x.max(lo).min(hi));
+    lisp.define_fn("shout", |s: String| s.to_uppercase());
+    lisp.define_fn("sum", |xs: Vec<i64>| xs.iter().sum::<i64>());
+    assert_eq!(
+        lisp.eval_str("(clamp 12 0 10)").unwrap(),
+        Expression::Number(Number::from(10.0))
+    );
+    assert_eq!(
+        lisp.eval_str("(shout \"hi\")").unwrap(),
+        Expression::Str("HI".to_string())
+    );
+    assert_eq!(lisp.eval_str("(sum (list 1 2 3))").unwrap(), int(6));
+}
+
+#[test]
+fn define_fn_reports_bad_calls() {
+    let lisp = Interpreter::new();
+    lisp.define_fn("clamp", |x: f64, lo: f64, hi: f64| x.max(lo).min(hi));
+    lisp.define_fn("checked", |n: i64| -> Result<i64, Error> {
+        if n < 0 {
+            Err(Error::user("negative"))
+        } else {
+            Ok(n)
+        }
+    });
+    assert_eq!(error_kind(&lisp, "(clamp 1 2)"), "arity");
+    assert_eq!(error_kind(&lisp, "(clamp \"a\" 0 1)"), "type");
+    assert_eq!(error_kind(&lisp, "(checked -1)"), "user");
+    assert_eq!(
+        lisp.eval_str("(try (checked -1) (catch e (error-message e)))")
+            .unwrap(),
+        Expression::Str("negative".to_string())
+    );
+}
+
+#[test]
+fn define_fn_takes_only_booleans_as_bool() {
+    let lisp = Interpreter::new();
+    lisp.define_fn("flip", |b: bool| !b);
+    assert_eq!(
+        lisp.eval_str("(flip true)").unwrap(),
+        Expression::Bool(false)
+    );
+    assert_eq!(
+        lisp.eval_str("(flip false)").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(error_kind(&lisp, "(flip 0)"), "type");
+    assert_eq!(error_kind(&lisp, "(flip nil)"), "type");
+    assert_eq!(error_kind(&lisp, "(flip \"yes\")"), "type");
+}
+
+#[test]
+fn define_fn_rejects_non_finite_results() {
+    let lisp = Interpreter::new();
+    lisp.define_fn("div", |a: f64, b: f64| a / b);
+    lisp.define_fn("spread", |x: f64| vec![x, x * f64::MAX]);
+    assert_eq!(error_kind(&lisp, "(div 1 0)"), "arithmetic");
+    assert_eq!(error_kind(&lisp, "(div 0 0)"), "arithmetic");
+    assert_eq!(error_kind(&lisp, "(spread 2)"), "arithmetic");
+    assert_eq!(
+        lisp.eval_str("(try (div 1 0) (catch e (error-message e)))")
+            .unwrap(),
+        Expression::Str("div: result out of range".to_string())
+    );
+    assert_eq!(
+        lisp.eval_str("(div 1 4)").unwrap(),
+        Expression::Number(Number::from(0.25))
+    );
+}
+
+#[test]
+fn call_applies_lisp_functions() {
+    let lisp = Interpreter::new();
+    lisp.eval_str("(def add (fn (a (b 1)) (+ a b)))").unwrap();
+    assert_eq!(lisp.call("add", &[int(1), int(2)]).unwrap(), int(3));
+    assert_eq!(lisp.call("add", &[int(1)]).unwrap(), int(2));
+    assert_eq!(lisp.call("+", &[int(1), int(2)]).unwrap(), int(3));
+    assert_eq!(lisp.call("add", &[]).unwrap_err().kind_name(), "arity");
+    assert_eq!(
+        lisp.call("missing", &[]).unwrap_err().kind_name(),
+        "unbound-symbol"
+    );
+}
+
+#[test]
+fn errors_have_kinds() {
+    let lisp = Interpreter::new();
+    assert_eq!(error_kind(&lisp, "undefined"), "unbound-symbol");
+    assert_eq!(error_kind(&lisp, "((fn (x) x))"), "arity");
+    assert_eq!(error_kind(&lisp, "(+ 1 \"a\")"), "type");
+    assert_eq!(error_kind(&lisp, "(/ 1 0)"), "arithmetic");
+    assert_eq!(error_kind(&lisp, "(+ 1"), "parse");
+    assert_eq!(error_kind(&lisp, "(error \"boom\")"), "user");
+    assert_eq!(error_kind(&lisp, "(throw 1)"), "thrown");
+}
+
+#[test]
+fn deep_recursion_fails_with_a_catchable_error() {
+    let lisp = Interpreter::new();
+    lisp.eval_str("(def down (fn (n) (if (= n 0) 0 (+ 1 (down (- n 1))))))")
+        .unwrap();
+    assert_eq!(lisp.eval_str("(down 10)").unwrap(), int(10));
+    assert_eq!(error_kind(&lisp, "(down 1000000)"), "stack-overflow");
+    assert_eq!(
+        lisp.eval_str("(try (down 1000000) (catch e (error-kind e)))")
+            .unwrap(),
+        Expression::Symbol("stack-overflow".to_string())
+    );
+    // The interpreter is still usable afterwards.
+    assert_eq!(lisp.eval_str("(down 10)").unwrap(), int(10));
+}
+
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    let lisp = Interpreter::new();
+    lisp.eval_str("(def count (fn (n acc) (if (= n 0) acc (count (- n 1) (+ acc 1)))))")
+        .unwrap();
+    assert_eq!(lisp.eval_str("(count 100000 0)").unwrap(), int(100000));
+    // Calls in tail position of cond, let, and, begin are tail calls too,
+    // including calls to another function.
+    lisp.eval_str(
+        "(def ev? (fn (n) (cond ((= n 0) true) (else (let ((m (- n 1))) (od? m))))))
+         (def od? (fn (n) (and (not (= n 0)) (begin (ev? (- n 1))))))",
+    )
+    .unwrap();
+    assert_eq!(
+        lisp.eval_str("(ev? 100001)").unwrap(),
+        Expression::Bool(false)
+    );
+}
+
+#[test]
+fn deeply_nested_source_fails_with_a_catchable_error() {
+    let lisp = Interpreter::new();
+    let calls = format!("{}1{}", "(list ".repeat(30000), ")".repeat(30000));
+    assert_eq!(error_kind(&lisp, &calls), "stack-overflow");
+    let quoted = format!("(quote {}{})", "(".repeat(30000), ")".repeat(30000));
+    assert_eq!(error_kind(&lisp, &quoted), "stack-overflow");
+    let read = format!(
+        "(try (read-string \"{}\") (catch e (error-kind e)))",
+        "(".repeat(30000)
+    );
+    assert_eq!(
+        lisp.eval_str(&read).unwrap(),
+        Expression::Symbol("stack-overflow".to_string())
+    );
+}
+
+#[test]
+fn deeply_nested_values_print_compare_and_drop() {
+    let lisp = Interpreter::new();
+    lisp.eval_str(
+        "(def nest (fn (n) (let loop ((i 0) (acc nil)) (if (= i n) acc (loop (+ i 1) (list acc))))))",
+    )
+    .unwrap();
+    let deep = lisp.eval_str("(nest 50000)").unwrap();
+    assert_eq!(deep.to_string().len(), 50000 * 2 + 3);
+    assert_eq!(deep, lisp.eval_str("(nest 50000)").unwrap());
+    assert_ne!(deep, lisp.eval_str("(nest 49999)").unwrap());
+    assert_eq!(
+        lisp.eval_str("(equal? (nest 50000) (nest 50000))").unwrap(),
+        Expression::Bool(true)
+    );
+}
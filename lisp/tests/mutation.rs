@@ -0,0 +1,70 @@
+use lisp::Interpreter;
+
+// The printed value of the last form in `source`.
+fn eval(source: &str) -> String {
+    Interpreter::new().eval_str(source).unwrap().to_string()
+}
+
+fn error_kind(source: &str) -> &'static str {
+    Interpreter::new().eval_str(source).unwrap_err().kind_name()
+}
+
+#[test]
+fn set_updates_the_innermost_binding() {
+    assert_eq!(eval("(def x 1) (set! x 2) x"), "2");
+    assert_eq!(eval("(def x 1) (let ((x 10)) (set! x 20)) x"), "1");
+    assert_eq!(eval("(def x 1) ((fn () (set! x 5))) x"), "5");
+    assert_eq!(error_kind("(set! undefined 1)"), "unbound-symbol");
+}
+
+#[test]
+fn boxes_and_arrays_are_shared_when_mutated() {
+    assert_eq!(
+        eval("(def b (box 1)) (def c b) (set-box! c 2) (unbox b)"),
+        "2"
+    );
+    assert_eq!(
+        eval("(def a (array 1 2)) (array-set! a 0 :x) (array-push! a 3) a"),
+        "#<array :x 2 3>"
+    );
+    assert_eq!(eval("(make-array 3 0)"), "#<array 0 0 0>");
+    assert_eq!(error_kind("(array-ref (array 1) 1)"), "type");
+    assert_eq!(error_kind("(make-array 1000000000000000 0)"), "arithmetic");
+}
+
+#[test]
+fn containers_that_contain_themselves_print() {
+    assert_eq!(
+        eval("(def b (box nil)) (set-box! b b) b"),
+        "#<box #<box ...>>"
+    );
+    assert_eq!(
+        eval("(def a (array 1 2)) (array-set! a 1 a) a"),
+        "#<array 1 #<array ...>>"
+    );
+    // A container that is only repeated, not nested in itself, prints whole.
+    assert_eq!(eval("(def b (box 1)) (list b b)"), "(#<box 1> #<box 1>)");
+}
+
+#[test]
+fn eq_compares_only_mutable_containers_by_identity() {
+    assert_eq!(eval("(eq? (box 1) (box 1))"), "false");
+    assert_eq!(eval("(eq? (array 1) (array 1))"), "false");
+    assert_eq!(eval("(def b (box 1)) (eq? b b)"), "true");
+    assert_eq!(eval("(eq? (list 1 2) (list 1 2))"), "true");
+    assert_eq!(eval("(eq? [1 2] (vector 1 2))"), "true");
+    assert_eq!(eval("(eq? {:a 1} (hash-map :a 1))"), "true");
+    assert_eq!(eval("(eq? [1 2] [1 3])"), "false");
+}
+
+#[test]
+fn containers_that_contain_themselves_compare() {
+    let source = "(def a (array 1)) (array-push! a a)
+                  (def b (array 1)) (array-push! b b)
+                  (def c (array 2)) (array-push! c c)";
+    let lisp = Interpreter::new();
+    lisp.eval_str(source).unwrap();
+    assert_eq!(lisp.eval_str("(equal? a b)").unwrap().to_string(), "true");
+    assert_eq!(lisp.eval_str("(equal? a c)").unwrap().to_string(), "false");
+    assert_eq!(lisp.eval_str("(eq? a b)").unwrap().to_string(), "false");
+}
@@ -0,0 +1,62 @@
+use lisp::Interpreter;
+
+// The printed value of the last form in `source`.
+fn eval(source: &str) -> String {
+    Interpreter::new().eval_str(source).unwrap().to_string()
+}
+
+#[test]
+fn when_and_unless() {
+    assert_eq!(eval("(when true 1 2)"), "2");
+    assert_eq!(eval("(when false 1)"), "nil");
+    assert_eq!(eval("(unless false 3)"), "3");
+    assert_eq!(eval("(unless true 3)"), "nil");
+}
+
+#[test]
+fn while_loops_until_the_test_fails() {
+    assert_eq!(
+        eval(
+            "(def i 0) (def acc nil) (while (< i 3) (set! acc (cons i acc)) (set! i (+ i 1))) acc"
+        ),
+        "(2 1 0)"
+    );
+    assert_eq!(eval("(while false 1)"), "nil");
+    assert_eq!(
+        eval("(def n 0) (while (< n 20000) (set! n (+ n 1))) n"),
+        "20000"
+    );
+}
+
+#[test]
+fn for_counts_up_to_the_bound() {
+    assert_eq!(
+        eval("(def acc nil) (for (i 0 4) (set! acc (cons i acc))) acc"),
+        "(3 2 1 0)"
+    );
+    assert_eq!(eval("(def acc nil) (for (i 2 2) (set! acc 1)) acc"), "nil");
+    assert_eq!(eval("(def n 0) (for (i 0 20000) (set! n i)) n"), "19999");
+    // Each iteration binds a fresh `i`.
+    assert_eq!(
+        eval("(def fs nil) (for (i 0 3) (set! fs (cons (fn () i) fs))) (map (fn (f) (f)) fs)"),
+        "(2 1 0)"
+    );
+}
+
+#[test]
+fn loops_are_hygienic() {
+    // The loop's own names don't capture the caller's.
+    assert_eq!(
+        eval("(def limit 7) (def acc nil) (for (i 0 2) (set! acc (cons limit acc))) acc"),
+        "(7 7)"
+    );
+    assert_eq!(
+        eval("(def loop 1) (def n 0) (while (< n 2) (set! n (+ n loop))) n"),
+        "2"
+    );
+    // And the caller's names don't capture the ones the loops use.
+    assert_eq!(
+        eval("(def acc nil) (let ((+ *) (< >)) (for (i 0 3) (set! acc (cons i acc)))) acc"),
+        "(2 1 0)"
+    );
+}
@@ -0,0 +1,49 @@
+use lisp::Interpreter;
+
+// Whether reading back the `repr` of the value of `source` gives an equal
+// value that prints the same.
+fn round_trips(source: &str) -> bool {
+    let lisp = Interpreter::new();
+    lisp.eval_str(&format!("(def v {})", source)).unwrap();
+    let same =
+        "(let ((back (read-string (repr v)))) (and (equal? back v) (equal? (repr back) (repr v))))";
+    lisp.eval_str(same).unwrap().to_string() == "true"
+}
+
+#[test]
+fn repr_reads_back_as_the_same_value() {
+    for source in [
+        "nil",
+        "true",
+        "'sym",
+        ":key",
+        "42",
+        "-0.0",
+        "0.1",
+        "(/ 1.0 3)",
+        "1e300",
+        "1/3",
+        "100000000000000000000000",
+        "\"tab\\t quote\\\" backslash\\\\ newline\\n\"",
+        "(list 1 (list 2 \"three\") '())",
+        "[1 [2] {:a nil}]",
+        "{:a [1 2] \"b\" '(c)}",
+    ] {
+        assert!(round_trips(source), "{} does not round-trip", source);
+    }
+}
+
+#[test]
+fn unreadable_values_fail_to_read_back() {
+    let lisp = Interpreter::new();
+    assert_eq!(
+        lisp.eval_str("(repr (box 1))").unwrap().to_string(),
+        "\"#<box 1>\""
+    );
+    assert_eq!(
+        lisp.eval_str("(read-string (repr (box 1)))")
+            .unwrap_err()
+            .kind_name(),
+        "parse"
+    );
+}